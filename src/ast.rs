@@ -5,13 +5,15 @@ use std::{
     sync::Arc,
 };
 
-use tower_lsp::lsp_types::Url;
+use semver::Version;
+use tower_lsp::lsp_types::{Range, Url};
 use tracing::{instrument, warn};
 
 use crate::{
     parse::Parse,
+    preproc::{self, Condition},
     query::{self, Decl, DeclKind, Index, NodeLocation, Query, Type},
-    zeek, Str,
+    zeek, zkg, Str,
 };
 
 #[salsa::query_group(AstStorage)]
@@ -22,6 +24,35 @@ pub trait Ast: Parse + Query {
     #[salsa::input]
     fn prefixes(&self) -> Arc<[PathBuf]>;
 
+    /// Zeek version used to evaluate conditional directives like `@if ( Version::at_least("6.0") )`.
+    #[salsa::input]
+    fn zeek_version(&self) -> Option<Version>;
+
+    /// Additional identifiers considered defined in `@ifdef` and `@ifndef`.
+    #[salsa::input]
+    fn defines(&self) -> Arc<[Str]>;
+
+    /// Whether to analyze scripts as if run with `zeek -b`.
+    #[salsa::input]
+    fn bare_mode(&self) -> bool;
+
+    /// zkg packages which can be loaded by name.
+    #[salsa::input]
+    fn packages(&self) -> Arc<[zkg::Package]>;
+
+    /// Get the ranges of inactive branches of conditional directives in a file.
+    #[must_use]
+    fn inactive_ranges(&self, uri: Arc<Url>) -> Arc<[Range]>;
+
+    /// Get the ranges of inactive branches of conditional directives in a file which are known
+    /// from the Zeek version and `defines` alone.
+    ///
+    /// Unlike `inactive_ranges` this does not look at declarations, so it can be used to decide
+    /// which `@load`s are active without depending on loaded files.
+    #[must_use]
+    fn load_inactive_ranges(&self, uri: Arc<Url>) -> Arc<[Range]>;
+
+    /// Get the files loaded by active `@load`s in a file.
     #[must_use]
     fn loaded_files(&self, url: Arc<Url>) -> Arc<[Arc<Url>]>;
 
@@ -38,6 +69,11 @@ pub trait Ast: Parse + Query {
     #[must_use]
     fn implicit_decls(&self) -> Arc<[Decl]>;
 
+    /// Get the decls which are only visible if Zeek loads its default scripts, i.e., outside of
+    /// bare mode.
+    #[must_use]
+    fn default_decls(&self) -> Arc<[Decl]>;
+
     #[must_use]
     fn possible_loads(&self, uri: Arc<Url>) -> Arc<[Str]>;
 
@@ -53,6 +89,19 @@ pub trait Ast: Parse + Query {
 
     /// Resolve type in a scope.
     fn resolve_type(&self, typ: Type, scope: Option<NodeLocation>) -> Option<Arc<Decl>>;
+
+    /// Get the members of an enum from its declaration and all redefs in known files.
+    ///
+    /// This includes members from files which are not visible from any particular file, see
+    /// `visible_enum_members` for the members usable in a file.
+    #[must_use]
+    fn enum_members(&self, fqid: Str) -> Arc<[Decl]>;
+
+    /// Get the members of an enum from its declaration and all redefs visible from a file.
+    ///
+    /// Members of the declaration come first, followed by members added in redefs.
+    #[must_use]
+    fn visible_enum_members(&self, uri: Arc<Url>, fqid: Str) -> Arc<[Decl]>;
 }
 
 #[instrument(skip(db))]
@@ -99,7 +148,7 @@ fn resolve_id(db: &dyn Ast, id: Str, scope: NodeLocation) -> Option<Arc<Decl>> {
             // redefs in the same file are only in effect after they have been declared.
             query::decls_(scope, Arc::clone(&uri), source.as_bytes())
                 .into_iter()
-                .filter(|d| d.id == id || d.fqid == id)
+                .filter(|d| (d.id == id || d.fqid == id) && is_active(db, d))
                 .filter(|d| {
                     let Some(loc) = &d.loc else { return false };
                     loc.range.start <= node.range().start
@@ -145,7 +194,7 @@ fn resolve_id(db: &dyn Ast, id: Str, scope: NodeLocation) -> Option<Arc<Decl>> {
             .iter()
             .chain(implicit_decls.iter())
             .chain(explicit_decls_recursive.iter())
-            .filter(|d| d.fqid == id)
+            .filter(|d| d.fqid == id && is_active(db, d))
             .collect::<Vec<_>>();
 
         // Prefer to return the decl instead of the definition for constructs which support both.
@@ -207,6 +256,7 @@ fn resolve_type(db: &dyn Ast, typ: Type, scope: Option<NodeLocation>) -> Option<
             is_export: None,
             loc: None,
             documentation: format!("Builtin type '{id}'").as_str().into(),
+            attrs: Vec::new(),
         })
     }
 
@@ -561,17 +611,26 @@ fn loaded_files(db: &dyn Ast, uri: Arc<Url>) -> Arc<[Arc<Url>]> {
     let files = db.files();
 
     let prefixes = db.prefixes();
+    let packages = db.packages();
+
+    // Loads in inactive branches of conditional directives are not followed.
+    let inactive = db.load_inactive_ranges(Arc::clone(&uri));
 
     let loads: Vec<_> = db
         .loads(Arc::clone(&uri))
         .iter()
-        .map(|load| PathBuf::from(load.as_str()))
+        .filter(|(_, range)| {
+            !inactive
+                .iter()
+                .any(|r| r.start <= range.start && range.end <= r.end)
+        })
+        .map(|(load, _)| PathBuf::from(load.as_str()))
         .collect();
 
     let mut loaded_files = Vec::new();
 
     for load in &loads {
-        if let Some(f) = load_to_file(load, uri.as_ref(), &files, &prefixes) {
+        if let Some(f) = load_to_file(load, uri.as_ref(), &files, &prefixes, &packages) {
             loaded_files.push(f);
         }
     }
@@ -608,24 +667,115 @@ fn loaded_files_recursive(db: &dyn Ast, url: Arc<Url>) -> Arc<[Arc<Url>]> {
 
 #[instrument(skip(db))]
 fn explicit_decls_recursive(db: &dyn Ast, uri: Arc<Url>) -> Arc<[Decl]> {
-    let mut decls: FxHashSet<_> = db.decls(Arc::clone(&uri)).iter().cloned().collect();
+    let mut decls: FxHashSet<_> = db
+        .decls(Arc::clone(&uri))
+        .iter()
+        .filter(|d| is_active(db, d))
+        .cloned()
+        .collect();
 
     for load in db.loaded_files_recursive(uri).as_ref() {
         for decl in &*db.decls(Arc::clone(load)) {
-            decls.insert(decl.clone());
+            if is_active(db, decl) {
+                decls.insert(decl.clone());
+            }
         }
     }
 
     Arc::from(decls.into_iter().collect::<Vec<_>>())
 }
 
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(skip(db))]
+fn inactive_ranges(db: &dyn Ast, uri: Arc<Url>) -> Arc<[Range]> {
+    let Some(source) = db.source(Arc::clone(&uri)) else {
+        return Arc::default();
+    };
+
+    let version = db.zeek_version();
+    let defines = db.defines();
+
+    // Identifiers are defined if they were explicitly defined by the user, or if we can see a
+    // declaration for them. Since this is used to filter declarations we look at unfiltered
+    // declarations here. If we cannot find an identifier we do not know whether it is undefined
+    // since our view of loaded files might be incomplete, so we do not evaluate the condition.
+    let is_defined = |id: &str| -> Option<bool> {
+        if defines.iter().any(|d| d == id) {
+            return Some(true);
+        }
+
+        let mut files = vec![Arc::clone(&uri)];
+        files.extend(db.loaded_files_recursive(Arc::clone(&uri)).iter().cloned());
+        for f in db.implicit_loads().iter() {
+            files.push(Arc::clone(f));
+            files.extend(db.loaded_files_recursive(Arc::clone(f)).iter().cloned());
+        }
+
+        for f in files {
+            if db.decls(f).iter().any(|d| d.fqid == id) {
+                return Some(true);
+            }
+        }
+
+        None
+    };
+
+    let ranges = preproc::inactive_ranges(&source, |cond| match cond {
+        Condition::If(expr) => preproc::eval_if(expr, version.as_ref()),
+        Condition::Ifdef(id) => is_defined(id),
+        Condition::Ifndef(id) => is_defined(id).map(|d| !d),
+    });
+
+    Arc::from(ranges)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(skip(db))]
+fn load_inactive_ranges(db: &dyn Ast, uri: Arc<Url>) -> Arc<[Range]> {
+    let Some(source) = db.source(uri) else {
+        return Arc::default();
+    };
+
+    let version = db.zeek_version();
+    let defines = db.defines();
+
+    // Without looking at declarations we only know whether explicitly defined identifiers are
+    // defined.
+    let is_defined = |id: &str| defines.iter().any(|d| d == id).then_some(true);
+
+    let ranges = preproc::inactive_ranges(&source, |cond| match cond {
+        Condition::If(expr) => preproc::eval_if(expr, version.as_ref()),
+        Condition::Ifdef(id) => is_defined(id),
+        Condition::Ifndef(id) => is_defined(id).map(|d| !d),
+    });
+
+    Arc::from(ranges)
+}
+
+/// Whether a decl is outside of inactive branches of conditional directives.
+pub(crate) fn is_active(db: &dyn Ast, decl: &Decl) -> bool {
+    let Some(loc) = &decl.loc else {
+        return true;
+    };
+
+    !db.inactive_ranges(Arc::clone(&loc.uri))
+        .iter()
+        .any(|r| r.start <= loc.range.start && loc.range.end <= r.end)
+}
+
 #[instrument(skip(db))]
 fn implicit_loads(db: &dyn Ast) -> Arc<[Arc<Url>]> {
+    let inputs = zeek::essential_input_files(db.zeek_version().as_ref(), db.bare_mode());
+    Arc::from(find_essential_inputs(db, &inputs))
+}
+
+/// Find the files for the given essential inputs under the known prefixes.
+fn find_essential_inputs(db: &dyn Ast, essential_inputs: &[&str]) -> Vec<Arc<Url>> {
     let mut loads = Vec::new();
 
     // These loops looks horrible, but is okay since this function will be cached most of the time
     // (unless global state changes).
-    for essential_input in zeek::essential_input_files() {
+    for essential_input in essential_inputs {
         let mut implicit_file = None;
         for f in &*db.files() {
             let Ok(path) = f.to_file_path() else { continue };
@@ -649,7 +799,7 @@ fn implicit_loads(db: &dyn Ast) -> Arc<[Arc<Url>]> {
         }
     }
 
-    Arc::from(loads)
+    loads
 }
 
 #[instrument(skip(db))]
@@ -669,6 +819,89 @@ fn implicit_decls(db: &dyn Ast) -> Arc<[Decl]> {
     Arc::from(decls)
 }
 
+#[instrument(skip(db))]
+fn default_decls(db: &dyn Ast) -> Arc<[Decl]> {
+    let version = db.zeek_version();
+    let bare_inputs = zeek::essential_input_files(version.as_ref(), true);
+    let default_inputs = zeek::essential_input_files(version.as_ref(), false)
+        .into_iter()
+        .filter(|f| !bare_inputs.contains(f))
+        .collect::<Vec<_>>();
+
+    let mut bare_decls = FxHashSet::default();
+    for f in find_essential_inputs(db, &bare_inputs) {
+        bare_decls.extend(db.explicit_decls_recursive(f).iter().cloned());
+    }
+
+    let mut decls = FxHashSet::default();
+    for f in find_essential_inputs(db, &default_inputs) {
+        decls.extend(
+            db.explicit_decls_recursive(f)
+                .iter()
+                .filter(|d| !bare_decls.contains(*d))
+                .cloned(),
+        );
+    }
+
+    Arc::from(decls.into_iter().collect::<Vec<_>>())
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(skip(db))]
+fn enum_members(db: &dyn Ast, fqid: Str) -> Arc<[Decl]> {
+    let members: FxHashSet<_> = db
+        .files()
+        .iter()
+        .flat_map(|f| {
+            db.decls(Arc::clone(f))
+                .iter()
+                .filter(|d| d.fqid == fqid && is_active(db, d))
+                .filter_map(|d| match &d.kind {
+                    DeclKind::Enum(members) | DeclKind::RedefEnum(members) => Some(members.clone()),
+                    _ => None,
+                })
+                .flatten()
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    Arc::from(
+        members
+            .into_iter()
+            .sorted_by(|a, b| a.fqid.cmp(&b.fqid))
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(skip(db))]
+fn visible_enum_members(db: &dyn Ast, uri: Arc<Url>, fqid: Str) -> Arc<[Decl]> {
+    let explicit = db.explicit_decls_recursive(uri);
+    let implicit = db.implicit_decls();
+
+    Arc::from(
+        explicit
+            .iter()
+            .chain(implicit.iter())
+            .filter(|d| d.fqid == fqid)
+            .filter_map(|d| match &d.kind {
+                DeclKind::Enum(members) => Some((0, d, members)),
+                DeclKind::RedefEnum(members) => Some((1, d, members)),
+                _ => None,
+            })
+            // Present members of the declaration first, and redefs in a stable order after.
+            .sorted_by_key(|(order, d, _)| {
+                (
+                    *order,
+                    d.loc.as_ref().map(|l| ((*l.uri).clone(), l.range.start)),
+                )
+            })
+            .flat_map(|(_, _, members)| members.iter().cloned())
+            .unique_by(|m| m.fqid.clone())
+            .collect::<Vec<_>>(),
+    )
+}
+
 #[instrument(skip(db))]
 fn possible_loads(db: &dyn Ast, uri: Arc<Url>) -> Arc<[Str]> {
     let Ok(path) = uri.to_file_path() else {
@@ -685,31 +918,34 @@ fn possible_loads(db: &dyn Ast, uri: Arc<Url>) -> Arc<[Str]> {
     let loads: Vec<_> = files
         .iter()
         .filter(|f| f.path() != uri.path())
-        .filter_map(|f| {
-            // Always strip any extension.
-            let f = f.to_file_path().ok()?.with_extension("");
-
-            // For `__load__.zeek` files one should use the directory name for loading.
-            let f = if f.file_stem()? == "__load__" {
-                f.parent()?
-            } else {
-                &f
-            };
-
-            if let Ok(f) = f.strip_prefix(path) {
-                Some(Str::from(Path::new(".").join(f).to_str()?))
-            } else {
-                prefixes.iter().find_map(|p| {
-                    let l = f.strip_prefix(p).ok()?.to_str()?;
-                    Some(Str::from(l))
-                })
-            }
-        })
+        .filter_map(|f| file_to_load(f, path, &prefixes))
         .collect();
 
     Arc::from(loads)
 }
 
+/// Compute the string to use in a `@load` in a file in `dir` to load `file`.
+pub(crate) fn file_to_load(file: &Url, dir: &Path, prefixes: &[PathBuf]) -> Option<Str> {
+    // Always strip any extension.
+    let f = file.to_file_path().ok()?.with_extension("");
+
+    // For `__load__.zeek` files one should use the directory name for loading.
+    let f = if f.file_stem()? == "__load__" {
+        f.parent()?
+    } else {
+        &f
+    };
+
+    if let Ok(f) = f.strip_prefix(dir) {
+        Some(Str::from(Path::new(".").join(f).to_str()?))
+    } else {
+        prefixes.iter().find_map(|p| {
+            let l = f.strip_prefix(p).ok()?.to_str()?;
+            Some(Str::from(l))
+        })
+    }
+}
+
 #[must_use]
 pub fn is_redef(d: &Decl) -> bool {
     matches!(
@@ -747,7 +983,22 @@ pub(crate) fn load_to_file(
     base: &Url,
     files: &[Arc<Url>],
     prefixes: &[PathBuf],
+    packages: &[zkg::Package],
 ) -> Option<Arc<Url>> {
+    // Packages in the workspace can be loaded by name.
+    if let Some(load_file) = packages
+        .iter()
+        .find(|p| load == Path::new(p.name.as_str()))
+        .map(zkg::Package::load_file)
+    {
+        if let Some(f) = files
+            .iter()
+            .find(|f| f.to_file_path().is_ok_and(|f| f == load_file))
+        {
+            return Some(Arc::clone(f));
+        }
+    }
+
     let file_dir = base
         .to_file_path()
         .ok()
@@ -758,43 +1009,51 @@ pub(crate) fn load_to_file(
         Err(_) => load,
     };
 
-    file_dir.iter().chain(prefixes.iter()).find_map(|prefix| {
-        // Files in the given prefix.
-        let files: Vec<_> = files
-            .iter()
-            .filter_map(|f| {
-                if let Ok(p) = f.to_file_path().ok()?.strip_prefix(prefix) {
-                    Some((f, p.to_path_buf()))
-                } else {
-                    None
-                }
-            })
-            .collect();
+    // Files embedded into a btest test are analyzed below the test, see `btest::embedded_uri`.
+    // btest runs the test in the directory it writes them to, so loads from the test see them.
+    let embedded_dir = base.to_file_path().ok();
 
-        // File known w/ extension.
-        let known_exactly = files.iter().find(|(_, p)| p.ends_with(load));
+    embedded_dir
+        .iter()
+        .chain(file_dir.iter())
+        .chain(prefixes.iter())
+        .find_map(|prefix| {
+            // Files in the given prefix.
+            let files: Vec<_> = files
+                .iter()
+                .filter_map(|f| {
+                    if let Ok(p) = f.to_file_path().ok()?.strip_prefix(prefix) {
+                        Some((f, p.to_path_buf()))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
 
-        let load_with_extension = {
-            let mut l = load.as_os_str().to_owned();
-            l.push(".zeek");
-            PathBuf::from(l)
-        };
+            // File known w/ extension.
+            let known_exactly = files.iter().find(|(_, p)| p.ends_with(load));
 
-        // File known w/o extension.
-        let known_no_ext = files
-            .iter()
-            .find(|(_, p)| p.ends_with(&load_with_extension));
+            let load_with_extension = {
+                let mut l = load.as_os_str().to_owned();
+                l.push(".zeek");
+                PathBuf::from(l)
+            };
 
-        // Load is directory with `__load__.zeek`.
-        let known_directory = files
-            .iter()
-            .find(|(_, p)| p.ends_with(load.join("__load__.zeek")));
+            // File known w/o extension.
+            let known_no_ext = files
+                .iter()
+                .find(|(_, p)| p.ends_with(&load_with_extension));
 
-        known_exactly
-            .or(known_no_ext)
-            .or(known_directory)
-            .map(|(f, _)| Arc::clone(f))
-    })
+            // Load is directory with `__load__.zeek`.
+            let known_directory = files
+                .iter()
+                .find(|(_, p)| p.ends_with(load.join("__load__.zeek")));
+
+            known_exactly
+                .or(known_no_ext)
+                .or(known_directory)
+                .map(|(f, _)| Arc::clone(f))
+        })
 }
 
 #[cfg(test)]
@@ -804,6 +1063,7 @@ mod test {
     use std::{path::PathBuf, str::FromStr, sync::Arc};
 
     use insta::assert_debug_snapshot;
+    use semver::Version;
     use tower_lsp::lsp_types::{Position, Range, Url};
 
     use crate::{
@@ -811,7 +1071,7 @@ mod test {
         lsp::TestDatabase,
         parse::Parse,
         query::{self, DeclKind, NodeLocation},
-        Files,
+        zkg, Files, Str,
     };
 
     #[test]
@@ -865,6 +1125,50 @@ mod test {
         assert_debug_snapshot!(db.0.loaded_files(foo));
     }
 
+    #[test]
+    fn loaded_files_conditional() {
+        let mut db = TestDatabase::default();
+        let a = Arc::new(Url::from_file_path("/a.zeek").unwrap());
+        let b = Arc::new(Url::from_file_path("/b.zeek").unwrap());
+        db.add_file((*a).clone(), "");
+        db.add_file((*b).clone(), "");
+
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            "@ifdef ( FOO )\n@load ./a\n@else\n@load ./b\n@endif\n",
+        );
+
+        // Without knowing whether `FOO` is defined both loads are followed.
+        assert_eq!(
+            db.0.loaded_files(Arc::clone(&uri)).as_ref(),
+            [Arc::clone(&a), Arc::clone(&b)]
+        );
+
+        db.0.set_defines(Arc::from([Str::from("FOO")]));
+        assert_eq!(db.0.loaded_files(Arc::clone(&uri)).as_ref(), [a]);
+    }
+
+    #[test]
+    fn loaded_files_package() {
+        let mut db = TestDatabase::default();
+
+        let package = zkg::Package::parse(
+            &PathBuf::from("/ws/my-package"),
+            "[package]\nscript_dir = scripts\n",
+        )
+        .unwrap();
+        db.0.set_packages(Arc::from([package]));
+
+        let load = Url::from_file_path("/ws/my-package/scripts/__load__.zeek").unwrap();
+        db.add_file(load.clone(), "");
+
+        let uri = Arc::new(Url::from_file_path("/x/x.zeek").unwrap());
+        db.add_file((*uri).clone(), "@load my-package");
+
+        assert_eq!(db.0.loaded_files(uri).as_ref(), [Arc::new(load)]);
+    }
+
     #[test]
     fn resolve() {
         let mut db = TestDatabase::default();
@@ -980,6 +1284,63 @@ x$f;",
         assert_debug_snapshot!(db.resolve(NodeLocation::from_node(uri, node)));
     }
 
+    #[test]
+    fn conditional_directives() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+
+        db.add_file(
+            (*uri).clone(),
+            r#"@if ( Version::at_least("6.0") )
+global x: count;
+@else
+global x: string;
+@endif
+@ifdef ( FOO )
+global y: count;
+@endif
+@ifndef ( x )
+global z: count;
+@endif
+x;"#,
+        );
+
+        let typ = |db: &TestDatabase| {
+            let tree = db.0.parse(Arc::clone(&uri)).unwrap();
+            let node = tree
+                .root_node()
+                .named_descendant_for_position(Position::new(11, 0))
+                .unwrap();
+            let decl =
+                db.0.resolve(NodeLocation::from_node(Arc::clone(&uri), node))?;
+            db.0.typ(decl).map(|t| t.fqid.to_string())
+        };
+
+        // Without a known version we cannot evaluate `@if`, but `x` is known.
+        assert_eq!(
+            db.0.inactive_ranges(Arc::clone(&uri)).as_ref(),
+            [Range::new(Position::new(9, 0), Position::new(10, 0))]
+        );
+
+        db.0.set_zeek_version(Some(Version::new(6, 0, 0)));
+        db.0.set_defines(Arc::from([Str::from("FOO")]));
+        assert_eq!(
+            db.0.inactive_ranges(Arc::clone(&uri)).as_ref(),
+            [
+                Range::new(Position::new(3, 0), Position::new(4, 0)),
+                Range::new(Position::new(9, 0), Position::new(10, 0))
+            ]
+        );
+        assert_eq!(typ(&db), Some("count".into()));
+
+        db.0.set_zeek_version(Some(Version::new(5, 2, 0)));
+        assert_eq!(
+            db.0.inactive_ranges(Arc::clone(&uri)).first(),
+            Some(&Range::new(Position::new(1, 0), Position::new(2, 0)))
+        );
+        assert_eq!(typ(&db), Some("string".into()));
+    }
+
     #[test]
     fn resolve_elsewhere() {
         let mut db = TestDatabase::default();
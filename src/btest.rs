@@ -0,0 +1,401 @@
+use std::{
+    path::{Path, PathBuf},
+    str,
+    sync::LazyLock,
+};
+
+use eyre::{eyre, Result};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range, Url};
+
+use crate::{zkg, Str};
+
+/// Name of the btest configuration file.
+pub const CONFIG_FILE: &str = "btest.cfg";
+
+/// A command of a btest test, e.g., `# @TEST-EXEC: zeek -b %INPUT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    /// Kind of the command, e.g., `TEST-EXEC` or `TEST-EXEC-FAIL`.
+    pub kind: Str,
+
+    /// The command to execute.
+    pub command: Str,
+
+    /// Line of the command in the test file.
+    pub line: u32,
+}
+
+/// Get the commands of a btest test.
+#[must_use]
+pub fn commands(source: &str) -> Vec<Command> {
+    static COMMAND: LazyLock<regex::Regex> = LazyLock::new(|| {
+        regex::Regex::new(r"^\s*#\s*@(TEST-EXEC(?:-FAIL)?):\s*(.*)$").expect("valid regex")
+    });
+
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let cap = COMMAND.captures(text)?;
+            Some(Command {
+                kind: Str::from(&cap[1]),
+                command: Str::from(cap[2].trim()),
+                line: u32::try_from(line).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Check whether the source is a btest test.
+#[must_use]
+pub fn is_test(source: &str) -> bool {
+    !commands(source).is_empty()
+}
+
+/// A file embedded into a btest test with `@TEST-START-FILE` and `@TEST-END-FILE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedFile {
+    pub name: Str,
+
+    /// Line of the first line of content in the test file.
+    pub start_line: u32,
+
+    /// Content of the embedded file.
+    pub source: String,
+}
+
+/// Get the files embedded into a btest test.
+#[must_use]
+pub fn embedded_files(source: &str) -> Vec<EmbeddedFile> {
+    let mut files = Vec::new();
+    let mut current: Option<EmbeddedFile> = None;
+
+    for (line, text) in source.lines().enumerate() {
+        if let Some(name) = start_file(text) {
+            let Ok(line) = u32::try_from(line) else {
+                break;
+            };
+
+            current = Some(EmbeddedFile {
+                name: Str::from(name),
+                start_line: line + 1,
+                source: String::new(),
+            });
+        } else if text.contains("@TEST-END-FILE") {
+            files.extend(current.take());
+        } else if let Some(file) = &mut current {
+            file.source.push_str(text);
+            file.source.push('\n');
+        }
+    }
+
+    files
+}
+
+fn start_file(line: &str) -> Option<&str> {
+    let (_, rest) = line.split_once("@TEST-START-FILE")?;
+    rest.split_whitespace().next()
+}
+
+/// Get the source of a btest test without the content of its embedded files.
+///
+/// Lines of embedded files are blanked so positions in the test are preserved.
+#[must_use]
+pub fn host_source(source: &str) -> String {
+    let mut in_file = false;
+
+    source
+        .lines()
+        .map(|text| {
+            if start_file(text).is_some() {
+                in_file = true;
+                text
+            } else if text.contains("@TEST-END-FILE") {
+                in_file = false;
+                text
+            } else if in_file {
+                ""
+            } else {
+                text
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Get the URI under which an embedded file of a test is analyzed.
+///
+/// Embedded files are placed "below" the test file so they cannot collide with files on disk.
+#[must_use]
+pub fn embedded_uri(test: &Url, name: &str) -> Option<Url> {
+    Url::from_file_path(test.to_file_path().ok()?.join(name)).ok()
+}
+
+/// A btest configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Directory containing the configuration file.
+    pub dir: PathBuf,
+
+    /// Directories containing tests.
+    pub test_dirs: Vec<PathBuf>,
+}
+
+impl Config {
+    /// Parse a configuration from the contents of a `btest.cfg` in `dir`.
+    #[must_use]
+    pub fn parse(dir: &Path, source: &str) -> Self {
+        let test_dirs = zkg::ini(source)
+            .into_iter()
+            .find(|(section, key, _)| section == "btest" && key.eq_ignore_ascii_case("TestDirs"))
+            .map(|(_, _, value)| {
+                value
+                    .split_whitespace()
+                    .map(|d| dir.join(d))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        Self {
+            dir: dir.to_path_buf(),
+            test_dirs,
+        }
+    }
+
+    /// Check whether a file is in one of the test directories of this configuration.
+    #[must_use]
+    pub fn contains(&self, file: &Path) -> bool {
+        self.test_dirs.is_empty() || self.test_dirs.iter().any(|d| file.starts_with(d))
+    }
+}
+
+/// Find the closest configuration for a test file.
+pub async fn find_config(file: &Path) -> Option<Config> {
+    for dir in file.ancestors().skip(1) {
+        if let Ok(source) = tokio::fs::read_to_string(dir.join(CONFIG_FILE)).await {
+            return Some(Config::parse(dir, &source));
+        }
+    }
+
+    None
+}
+
+/// Outcome of running a test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub passed: bool,
+
+    /// The command which failed, if known.
+    pub failed_command: Option<String>,
+
+    /// Diagnostic output of btest.
+    pub output: String,
+}
+
+/// Run a test with btest.
+///
+/// # Arguments
+///
+/// * `config` - configuration the test belongs to
+/// * `test` - path to the test file
+/// * `update_baseline` - whether to update the baseline of the test
+///
+/// # Errors
+///
+/// Will return `Err` if `btest` cannot be run.
+pub async fn run(config: &Config, test: &Path, update_baseline: bool) -> Result<Outcome> {
+    let test = test.strip_prefix(&config.dir).unwrap_or(test);
+
+    let mut command = tokio::process::Command::new("btest");
+    command.current_dir(&config.dir).arg("-d");
+    if update_baseline {
+        command.arg("-U");
+    }
+
+    let output = command
+        .arg(test)
+        .output()
+        .await
+        .map_err(|_| eyre!("btest not found in PATH"))?;
+
+    let output = format!(
+        "{}{}",
+        str::from_utf8(&output.stdout)?,
+        str::from_utf8(&output.stderr)?
+    )
+    .trim()
+    .to_string();
+
+    Ok(parse_output(&output))
+}
+
+fn parse_output(output: &str) -> Outcome {
+    static FAILED: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new(r"% '(.*)' failed unexpectedly").expect("valid regex"));
+
+    let failed_command = FAILED.captures(output).map(|cap| cap[1].to_string());
+
+    let passed = failed_command.is_none()
+        && output
+            .lines()
+            .any(|l| l.ends_with("... ok") || l.contains("tests successful"));
+
+    Outcome {
+        passed,
+        failed_command,
+        output: output.to_string(),
+    }
+}
+
+/// Check whether an executed command was produced by a test command.
+///
+/// btest substitutes macros like `%INPUT` before executing commands, so only the remaining parts
+/// of the test command are matched.
+fn matches(command: &str, executed: &str) -> bool {
+    static MACRO: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new(r"%[A-Z_]+").expect("valid regex"));
+
+    let mut rest = executed;
+    for part in MACRO.split(command) {
+        let Some(idx) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[idx + part.len()..];
+    }
+
+    true
+}
+
+/// Convert the outcome of a test run into diagnostics on the commands of the test.
+#[must_use]
+pub fn diagnostics(source: &str, outcome: &Outcome) -> Vec<Diagnostic> {
+    let commands = commands(source);
+
+    let diagnostic = |command: &Command, severity, message: String| {
+        let range = Range::new(
+            Position::new(command.line, 0),
+            Position::new(command.line, u32::MAX),
+        );
+        Diagnostic::new(
+            range,
+            Some(severity),
+            None,
+            Some("btest".to_string()),
+            message,
+            None,
+            None,
+        )
+    };
+
+    if outcome.passed {
+        return commands
+            .iter()
+            .map(|c| diagnostic(c, DiagnosticSeverity::INFORMATION, "test passed".into()))
+            .collect();
+    }
+
+    // Attribute the failure to the command which failed, or the first command if we cannot tell.
+    let failed = outcome
+        .failed_command
+        .as_ref()
+        .and_then(|executed| commands.iter().find(|c| matches(&c.command, executed)))
+        .or_else(|| commands.first());
+
+    failed
+        .map(|c| {
+            diagnostic(
+                c,
+                DiagnosticSeverity::ERROR,
+                format!("test failed\n\n{}", outcome.output),
+            )
+        })
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::path::{Path, PathBuf};
+
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Url};
+
+    use crate::btest::{
+        commands, diagnostics, embedded_files, embedded_uri, host_source, parse_output, Config,
+    };
+
+    const TEST: &str = "# @TEST-EXEC: zeek -b %INPUT >output
+# @TEST-EXEC: btest-diff output
+
+@load ./lib
+
+event zeek_init() { print 1; }
+
+@TEST-START-FILE lib.zeek
+global x = 1;
+@TEST-END-FILE
+";
+
+    #[test]
+    fn parse() {
+        let commands = commands(TEST);
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].kind, "TEST-EXEC");
+        assert_eq!(commands[0].command, "zeek -b %INPUT >output");
+        assert_eq!(commands[1].line, 1);
+
+        let files = embedded_files(TEST);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "lib.zeek");
+        assert_eq!(files[0].start_line, 8);
+        assert_eq!(files[0].source, "global x = 1;\n");
+
+        let host = host_source(TEST);
+        assert_eq!(host.lines().count(), TEST.lines().count());
+        assert!(!host.contains("global x"));
+        assert!(host.contains("@TEST-START-FILE lib.zeek"));
+
+        assert_eq!(
+            embedded_uri(&Url::from_file_path("/t/a.zeek").unwrap(), "lib.zeek"),
+            Some(Url::from_file_path("/t/a.zeek/lib.zeek").unwrap())
+        );
+    }
+
+    #[test]
+    fn config() {
+        let config = Config::parse(
+            Path::new("/t"),
+            "[btest]\nTestDirs = tests other\nTmpDir = .tmp\n",
+        );
+        assert_eq!(
+            config.test_dirs,
+            [PathBuf::from("/t/tests"), PathBuf::from("/t/other")]
+        );
+        assert!(config.contains(Path::new("/t/tests/a.zeek")));
+        assert!(!config.contains(Path::new("/t/scripts/a.zeek")));
+    }
+
+    #[test]
+    fn outcome() {
+        let passed = parse_output("tests.a ... ok\nall 1 tests successful");
+        assert!(passed.passed);
+        let diags = diagnostics(TEST, &passed);
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].severity, Some(DiagnosticSeverity::INFORMATION));
+
+        let failed = parse_output(
+            "tests.a ... failed\n  % 'btest-diff output' failed unexpectedly (exit code 1)\n1 of 1 test failed",
+        );
+        assert!(!failed.passed);
+        let diags = diagnostics(TEST, &failed);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].range.start.line, 1);
+        assert_eq!(diags[0].severity, Some(DiagnosticSeverity::ERROR));
+
+        let failed = parse_output(
+            "tests.a ... failed\n  % 'zeek -b /t/tests/a.zeek >output' failed unexpectedly (exit code 1)",
+        );
+        assert_eq!(diagnostics(TEST, &failed)[0].range.start.line, 0);
+    }
+}
@@ -3,16 +3,17 @@ use std::sync::{Arc, LazyLock};
 
 use crate::{
     ast::{self, Ast},
-    lsp::Database,
+    lsp::{Database, FieldInfo, OPTIONAL_FIELD_HINT},
     parse::Parse,
     query::{self, Decl, DeclKind, Node, NodeLocation, Query},
-    Files, Str,
+    sig, typecheck, Files, Str,
 };
 
 use itertools::Itertools;
 use tower_lsp::lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionParams,
-    CompletionResponse, Documentation, InsertTextFormat, MarkupContent, Position, Url,
+    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionItemTag,
+    CompletionParams, CompletionResponse, Documentation, InsertTextFormat, MarkupContent, Position,
+    Url,
 };
 use tree_sitter_zeek::KEYWORDS;
 
@@ -23,10 +24,23 @@ pub(crate) fn complete(state: &Database, params: CompletionParams) -> Option<Com
 
     let source = state.source(Arc::clone(&uri))?;
 
+    if sig::is_sig_file(&uri) {
+        return complete_sig(state, &source, position).map(CompletionResponse::from);
+    }
+
     let tree = state.parse(Arc::clone(&uri))?;
+    let root = tree.root_node();
+
+    // If we are completing an attribute like `&optional` return all known attributes.
+    if let Some(items) = complete_attr(root, &source, position) {
+        return Some(CompletionResponse::from(items));
+    }
+
+    if let Some(items) = complete_enum_member(state, &uri, &source, position) {
+        return Some(CompletionResponse::from(items));
+    }
 
     // Get the node directly under the cursor as a starting point.
-    let root = tree.root_node();
     let mut node = root.descendant_for_position(position)?;
 
     let text = completion_text(node, &source, true);
@@ -229,13 +243,38 @@ fn complete_field(
                 return Some(
                     fields
                         .iter()
-                        .map(to_completion_item)
-                        .filter_map(|item| {
+                        .filter_map(|f| {
+                            let item = to_completion_item(f);
+
                             // By default we use FQIDs for completion labels. Since for
                             // record fields this would be e.g., `mod::rec::field` where we
                             // want just `field`, rework them slightly.
                             let label = item.label.split("::").last()?.to_string();
-                            Some(CompletionItem { label, ..item })
+
+                            // Show where the field comes from and how it is declared.
+                            let info = FieldInfo::new(state, f);
+                            let detail = Some(info.detail()).filter(|d| !d.is_empty());
+                            let label_details =
+                                info.file.clone().map(|file| CompletionItemLabelDetails {
+                                    detail: None,
+                                    description: Some(file),
+                                });
+                            let documentation = if info.optional {
+                                Some(Documentation::MarkupContent(MarkupContent {
+                                    kind: tower_lsp::lsp_types::MarkupKind::Markdown,
+                                    value: format!("{}\n\n{OPTIONAL_FIELD_HINT}", f.documentation),
+                                }))
+                            } else {
+                                item.documentation
+                            };
+
+                            Some(CompletionItem {
+                                label,
+                                detail,
+                                label_details,
+                                documentation,
+                                ..item
+                            })
                         })
                         .collect::<Vec<_>>(),
                 );
@@ -293,6 +332,170 @@ fn complete_from_decls(state: &Database, uri: Arc<Url>, kind: &str) -> Vec<Compl
         .collect::<Vec<_>>()
 }
 
+/// Complete attributes if the token before `position` is a possibly partial attribute like
+/// `&opt`.
+///
+/// Attributes are only offered where an attribute list can follow, i.e., after a type or other
+/// attributes, but not in comments, strings, or for the binary `&` operator.
+fn complete_attr(root: Node, source: &str, position: Position) -> Option<Vec<CompletionItem>> {
+    let token_before = |position: Position| {
+        let before = Position::new(position.line, position.character.checked_sub(1)?);
+        root.descendant_for_position(before)
+    };
+
+    let mut token = token_before(position)?;
+
+    let mut node = Some(token);
+    while let Some(n) = node {
+        if matches!(n.kind(), "string" | "pattern") || n.kind().ends_with("comment") {
+            return None;
+        }
+        node = n.parent();
+    }
+
+    let text = |n: Node| n.utf8_text(source.as_bytes()).ok();
+    let is_word = |t: &str| t.chars().all(|c| c.is_alphanumeric() || c == '_');
+
+    // For a partial attribute like `&opt` the cursor is on the identifier after the `&`.
+    if !text(token)?.starts_with('&') {
+        if !is_word(text(token)?) {
+            return None;
+        }
+        token = token_before(token.range().start)?;
+    }
+
+    if !text(token)?.strip_prefix('&').is_some_and(is_word) {
+        return None;
+    }
+
+    // A complete attribute was parsed as such.
+    let parent = token.parent()?;
+    if !matches!(parent.kind(), "attr" | "attr_list") {
+        // Otherwise this could be the binary `&` operator, or an incomplete attribute which did
+        // not parse. Only accept the latter if it follows a type or other attributes.
+        if parent.kind() == "expr" {
+            return None;
+        }
+
+        let mut node = token;
+        let prev = loop {
+            if let Some(prev) = node.prev_sibling() {
+                break prev;
+            }
+
+            node = node.parent().filter(|p| p.kind() == "ERROR")?;
+        };
+
+        if !matches!(prev.kind(), "type" | "attr" | "attr_list") {
+            return None;
+        }
+    }
+
+    Some(
+        query::ATTRIBUTES
+            .iter()
+            .map(|(name, docs)| CompletionItem {
+                label: format!("&{name}"),
+                kind: Some(CompletionItemKind::KEYWORD),
+                insert_text: Some((*name).to_string()),
+                filter_text: Some((*name).to_string()),
+                documentation: Some(Documentation::MarkupContent(MarkupContent {
+                    kind: tower_lsp::lsp_types::MarkupKind::Markdown,
+                    value: (*docs).to_string(),
+                })),
+                ..CompletionItem::default()
+            })
+            .collect(),
+    )
+}
+
+/// Complete enum members where only members of a known enum are valid.
+///
+/// This handles the `$note` field of notices, e.g., `NOTICE([$note=...])`, and `case` labels of
+/// `switch` statements over enum values.
+fn complete_enum_member(
+    state: &Database,
+    uri: &Arc<Url>,
+    source: &str,
+    position: Position,
+) -> Option<Vec<CompletionItem>> {
+    static NOTE: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new(r"\$note\s*=\s*[\w:]*$").expect("invalid regexp"));
+    static CASE: LazyLock<regex::Regex> = LazyLock::new(|| {
+        regex::Regex::new(r"^\s*case\s+([\w:]+\s*,\s*)*[\w:]*$").expect("invalid regexp")
+    });
+    let prefix = line_prefix(source, position)?;
+
+    let id = if NOTE.is_match(&prefix) {
+        Str::from("Notice::Type")
+    } else if CASE.is_match(&prefix) {
+        // Find the `switch` containing the position and get the type of its expression.
+        let tree = state.parse(Arc::clone(uri))?;
+        let node = tree.root_node().descendant_for_position(position)?;
+        let expr = typecheck::enclosing_switch_expr(node)?;
+        let typ = typecheck::switch_enum(state, uri, expr)?;
+
+        typ.fqid.clone()
+    } else {
+        return None;
+    };
+
+    let members = state.visible_enum_members(Arc::clone(uri), id);
+    if members.is_empty() {
+        return None;
+    }
+
+    Some(members.iter().map(to_completion_item).collect())
+}
+
+/// Complete in signature files.
+///
+/// Signature ids are completed after `requires-signature` and `requires-reverse-signature`.
+fn complete_sig(state: &Database, source: &str, position: Position) -> Option<Vec<CompletionItem>> {
+    static RE: LazyLock<regex::Regex> = LazyLock::new(|| {
+        regex::Regex::new(r"requires-(reverse-)?signature\s+(!\s*)?[\w-]*$")
+            .expect("invalid regexp")
+    });
+
+    if !RE.is_match(&line_prefix(source, position)?) {
+        return None;
+    }
+
+    Some(
+        state
+            .files()
+            .iter()
+            .filter_map(|f| state.parse_sig(Arc::clone(f)))
+            .flat_map(|f| {
+                f.signatures
+                    .iter()
+                    .map(|s| s.id.clone())
+                    .collect::<Vec<_>>()
+            })
+            .unique()
+            .map(|id| CompletionItem {
+                label: id.to_string(),
+                kind: Some(CompletionItemKind::REFERENCE),
+                ..CompletionItem::default()
+            })
+            .collect(),
+    )
+}
+
+/// Get the text on the line of `position` up to `position`.
+fn line_prefix(source: &str, position: Position) -> Option<String> {
+    let line = source
+        .lines()
+        .nth(usize::try_from(position.line).ok()?)
+        .unwrap_or_default();
+
+    Some(
+        line.chars()
+            .take(usize::try_from(position.character).ok()?)
+            .collect(),
+    )
+}
+
 fn complete_snippet(text: &str) -> Vec<CompletionItem> {
     let snippets = vec![
         (
@@ -526,6 +729,7 @@ fn to_completion_item(d: &Decl) -> CompletionItem {
             kind: tower_lsp::lsp_types::MarkupKind::Markdown,
             value: d.documentation.to_string(),
         })),
+        tags: d.deprecation().map(|_| vec![CompletionItemTag::DEPRECATED]),
         ..CompletionItem::default()
     }
 }
@@ -854,6 +1058,69 @@ mod test {
         ));
     }
 
+    #[test]
+    fn field_provenance() {
+        let mut db = TestDatabase::default();
+        db.add_file(
+            Url::from_file_path("/x.zeek").unwrap(),
+            "module X; export { type R: record { a: count &log; }; }",
+        );
+        db.add_file(
+            Url::from_file_path("/y.zeek").unwrap(),
+            "module Y; redef record X::R += { b: string &optional &log; };",
+        );
+
+        let uri = Url::from_file_path("/z.zeek").unwrap();
+        db.add_file(
+            uri.clone(),
+            "@load ./x
+@load ./y
+event foo(r: X::R) {
+    r$
+}",
+        );
+
+        let Some(CompletionResponse::Array(items)) = complete(
+            &db.0,
+            CompletionParams {
+                text_document_position: TextDocumentPositionParams::new(
+                    TextDocumentIdentifier::new(uri),
+                    Position::new(3, 6),
+                ),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+                context: None,
+            },
+        ) else {
+            panic!()
+        };
+
+        let details = items
+            .iter()
+            .map(|i| {
+                (
+                    i.label.as_str(),
+                    i.detail.as_deref(),
+                    i.label_details
+                        .as_ref()
+                        .and_then(|d| d.description.as_deref()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            details,
+            [
+                ("a", Some("count &log"), Some("/x.zeek")),
+                ("b", Some("string &optional &log"), Some("/y.zeek")),
+            ]
+        );
+
+        let Some(Documentation::MarkupContent(docs)) = &items[1].documentation else {
+            panic!()
+        };
+        assert!(docs.value.contains("?$"));
+    }
+
     #[test]
     fn referenced_field_access() {
         let mut db = TestDatabase::default();
@@ -1123,4 +1390,160 @@ event zeek_init() {
 
         // assert_debug_snapshot!(foo);
     }
+
+    #[test]
+    fn attr() {
+        let mut db = TestDatabase::default();
+        let uri = Url::from_file_path("/x.zeek").unwrap();
+        db.add_file(
+            uri.clone(),
+            r#"type X: record { x: count &opt };
+global y = T && F;
+# Comment &opt
+print "a &opt";
+global z = x &y;"#,
+        );
+
+        let complete = |line, character| {
+            complete(
+                &db.0,
+                CompletionParams {
+                    text_document_position: TextDocumentPositionParams::new(
+                        TextDocumentIdentifier::new(uri.clone()),
+                        Position::new(line, character),
+                    ),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                    context: None,
+                },
+            )
+        };
+
+        let Some(CompletionResponse::Array(result)) = complete(0, 30) else {
+            panic!()
+        };
+        let optional = result.iter().find(|i| i.label == "&optional").unwrap();
+        assert_eq!(optional.insert_text.as_deref(), Some("optional"));
+        assert!(optional.documentation.is_some());
+
+        let has_attrs = |line, character| {
+            let result = match complete(line, character) {
+                Some(CompletionResponse::Array(result)) => result,
+                _ => Vec::new(),
+            };
+            result.iter().any(|i| i.label.starts_with('&'))
+        };
+
+        // `&&` is not an attribute.
+        assert!(!has_attrs(1, 15));
+
+        // No attributes in comments or strings.
+        assert!(!has_attrs(2, 14));
+        assert!(!has_attrs(3, 13));
+
+        // The binary `&` operator is not an attribute.
+        assert!(!has_attrs(4, 15));
+    }
+
+    #[test]
+    fn enum_members() {
+        let mut db = TestDatabase::default();
+
+        db.add_file(
+            Url::from_file_path("/notice.zeek").unwrap(),
+            "module Notice;
+export {
+    type Type: enum { Tally };
+    type Info: record { note: Type; };
+    global NOTICE: function(n: Info);
+}",
+        );
+        db.add_file(
+            Url::from_file_path("/ssh.zeek").unwrap(),
+            "module SSH;
+redef enum Notice::Type += {
+    ## Docs.
+    Password_Guessing,
+};",
+        );
+        // Not loaded from the file, so its members should not be offered.
+        db.add_file(
+            Url::from_file_path("/ftp.zeek").unwrap(),
+            "module FTP;
+redef enum Notice::Type += { Bruteforcing };",
+        );
+
+        let uri = Url::from_file_path("/x.zeek").unwrap();
+        db.add_file(
+            uri.clone(),
+            "@load ./notice
+@load ./ssh
+type E: enum { A, B };
+event zeek_init() {
+    NOTICE([$note=]);
+    local e: E;
+    switch ( e ) {
+        case 
+    }
+}",
+        );
+
+        let complete = |line, character| {
+            let Some(CompletionResponse::Array(result)) = complete(
+                &db.0,
+                CompletionParams {
+                    text_document_position: TextDocumentPositionParams::new(
+                        TextDocumentIdentifier::new(uri.clone()),
+                        Position::new(line, character),
+                    ),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                    context: None,
+                },
+            ) else {
+                panic!()
+            };
+            result.into_iter().map(|i| i.label).collect::<Vec<_>>()
+        };
+
+        assert_eq!(complete(4, 18), ["Notice::Tally", "SSH::Password_Guessing"]);
+        assert_eq!(complete(7, 13), ["A", "B"]);
+    }
+
+    #[test]
+    fn sig() {
+        let mut db = TestDatabase::default();
+        let uri = Url::from_file_path("/x.sig").unwrap();
+        db.add_file(
+            uri.clone(),
+            "signature a { payload /a/ }
+signature b { requires-signature ! a }
+signature c { payload /c/ }",
+        );
+
+        let complete = |line, character| {
+            complete(
+                &db.0,
+                CompletionParams {
+                    text_document_position: TextDocumentPositionParams::new(
+                        TextDocumentIdentifier::new(uri.clone()),
+                        Position::new(line, character),
+                    ),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                    context: None,
+                },
+            )
+        };
+
+        let Some(CompletionResponse::Array(result)) = complete(1, 36) else {
+            panic!()
+        };
+        assert_eq!(
+            result.iter().map(|i| i.label.as_str()).collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+
+        assert_eq!(complete(0, 20), None);
+    }
 }
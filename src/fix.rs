@@ -0,0 +1,553 @@
+//! Quick fixes for diagnostics.
+//!
+//! Every diagnostic is offered to each fixer in [`FIXERS`], and all fixes they produce are
+//! returned. Fixers decide from the diagnostic's code, source or message whether they apply.
+
+use std::sync::{Arc, LazyLock};
+
+use itertools::Itertools;
+use regex::Regex;
+use tower_lsp::lsp_types::{Diagnostic, NumberOrString, Position, Range, TextEdit, Url};
+
+use crate::{
+    ast::Ast,
+    lint::{self, Rule},
+    lsp::{Database, ERROR_CODE_IS_MISSING},
+    parse::Parse,
+    query::Node,
+    typecheck, Files,
+};
+
+/// A fix for a diagnostic consisting of edits to the diagnostic's file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Fix {
+    pub(crate) title: String,
+    pub(crate) edits: Vec<TextEdit>,
+}
+
+impl Fix {
+    fn new(title: impl Into<String>, edit: TextEdit) -> Self {
+        Self {
+            title: title.into(),
+            edits: vec![edit],
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) use test::apply;
+
+type Fixer = fn(&Database, &Arc<Url>, &Diagnostic) -> Vec<Fix>;
+
+/// All known fixers.
+static FIXERS: &[Fixer] = &[
+    insert_missing,
+    match_handler_signature,
+    add_switch_cases,
+    guard_optional_field,
+    declare_local,
+    qualify_with_module,
+    replace_assignment_in_condition,
+    add_optional,
+    remove_load,
+];
+
+/// Computes all fixes for the given diagnostic.
+pub(crate) fn fixes(db: &Database, uri: &Arc<Url>, diagnostic: &Diagnostic) -> Vec<Fix> {
+    FIXERS
+        .iter()
+        .flat_map(|fixer| fixer(db, uri, diagnostic))
+        .collect()
+}
+
+fn has_code(diagnostic: &Diagnostic, code: i32) -> bool {
+    diagnostic.code == Some(NumberOrString::Number(code))
+}
+
+/// Insert the token reported as `MISSING` by the parser.
+fn insert_missing(db: &Database, uri: &Arc<Url>, diagnostic: &Diagnostic) -> Vec<Fix> {
+    if !has_code(diagnostic, ERROR_CODE_IS_MISSING) {
+        return Vec::new();
+    }
+
+    let Some(tree) = db.parse(Arc::clone(uri)) else {
+        return Vec::new();
+    };
+
+    tree.root_node()
+        .errors()
+        .into_iter()
+        // `kind` holds the fix for the `MISSING` error.
+        .filter(|err| err.is_missing() && err.range() == diagnostic.range)
+        .map(|err| {
+            let missing = err.kind();
+            Fix::new(
+                format!("Insert missing '{missing}'"),
+                TextEdit::new(diagnostic.range, missing.to_string()),
+            )
+        })
+        .collect()
+}
+
+/// Rewrite parameters of handlers not matching their declaration.
+fn match_handler_signature(db: &Database, uri: &Arc<Url>, diagnostic: &Diagnostic) -> Vec<Fix> {
+    if !Rule::HandlerSignature.matches(diagnostic) {
+        return Vec::new();
+    }
+
+    typecheck::handler_signature_fix(db, uri, diagnostic.range)
+        .map(|edit| Fix::new("Change parameters to match declaration", edit))
+        .into_iter()
+        .collect()
+}
+
+/// Add missing cases to switches over enums.
+fn add_switch_cases(db: &Database, uri: &Arc<Url>, diagnostic: &Diagnostic) -> Vec<Fix> {
+    if !Rule::NonExhaustiveSwitch.matches(diagnostic) {
+        return Vec::new();
+    }
+
+    typecheck::switch_cases_fix(db, uri, diagnostic.range)
+        .map(|edit| Fix::new("Add missing cases", edit))
+        .into_iter()
+        .collect()
+}
+
+/// Guard accesses to optional fields.
+fn guard_optional_field(db: &Database, uri: &Arc<Url>, diagnostic: &Diagnostic) -> Vec<Fix> {
+    if !Rule::OptionalFieldAccess.matches(diagnostic) {
+        return Vec::new();
+    }
+
+    lint::optional_field_guard_fix(db, uri, diagnostic.range)
+        .map(|edit| Fix::new("Check whether field is set before accessing it", edit))
+        .into_iter()
+        .collect()
+}
+
+/// Extract the identifier from an `unknown identifier` error reported by `zeek`.
+fn unknown_identifier(diagnostic: &Diagnostic) -> Option<&str> {
+    static UNKNOWN: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^unknown identifier ([A-Za-z_][A-Za-z_0-9:]*)").expect("invalid regex")
+    });
+
+    if diagnostic.source.as_deref() != Some("zeek") {
+        return None;
+    }
+
+    UNKNOWN
+        .captures(&diagnostic.message)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str())
+}
+
+/// Find all identifiers with the given name on a line.
+///
+/// Field names like `f` in `r$f` are not identifiers in their own right and are skipped.
+fn ids_on_line<'a>(node: Node<'a>, source: &[u8], line: u32, name: &str) -> Vec<Node<'a>> {
+    let range = node.range();
+    if range.start.line > line || range.end.line < line {
+        return Vec::new();
+    }
+
+    if node.kind() == "id" {
+        return if range.start.line == line
+            && node.utf8_text(source).is_ok_and(|t| t == name)
+            && !lint::is_field_name(node)
+        {
+            vec![node]
+        } else {
+            Vec::new()
+        };
+    }
+
+    node.named_children_not("nl")
+        .into_iter()
+        .flat_map(|c| ids_on_line(c, source, line, name))
+        .collect()
+}
+
+/// Declare an unknown identifier which is assigned to as a local.
+fn declare_local(db: &Database, uri: &Arc<Url>, diagnostic: &Diagnostic) -> Vec<Fix> {
+    let Some(name) = unknown_identifier(diagnostic).filter(|n| !n.contains("::")) else {
+        return Vec::new();
+    };
+
+    let (Some(tree), Some(source)) = (db.parse(Arc::clone(uri)), db.source(Arc::clone(uri))) else {
+        return Vec::new();
+    };
+
+    ids_on_line(
+        tree.root_node(),
+        source.as_bytes(),
+        diagnostic.range.start.line,
+        name,
+    )
+    .into_iter()
+    .filter_map(|id| {
+        // We need an assignment `name = ...` which forms a statement of its own.
+        let lhs = id.parent().filter(|p| p.kind() == "expr")?;
+        let assignment = lhs
+            .parent()
+            .filter(|p| typecheck::is_assignment(*p))
+            .filter(|p| p.children().first().map(Node::range) == Some(lhs.range()))?;
+        let stmt = assignment.parent().filter(|p| p.kind() == "stmt")?;
+
+        // Locals can only be declared in function bodies.
+        let mut node = stmt.parent();
+        while let Some(n) = node {
+            if matches!(n.kind(), "func_decl" | "event_decl" | "hook_decl") {
+                let start = stmt.range().start;
+                return Some(Fix::new(
+                    format!("Declare '{name}' as local"),
+                    TextEdit::new(Range::new(start, start), "local ".into()),
+                ));
+            }
+            node = n.parent();
+        }
+
+        None
+    })
+    .take(1)
+    .collect()
+}
+
+/// Qualify an unknown identifier with a module exporting it.
+fn qualify_with_module(db: &Database, uri: &Arc<Url>, diagnostic: &Diagnostic) -> Vec<Fix> {
+    let Some(name) = unknown_identifier(diagnostic).filter(|n| !n.contains("::")) else {
+        return Vec::new();
+    };
+
+    let (Some(tree), Some(source)) = (db.parse(Arc::clone(uri)), db.source(Arc::clone(uri))) else {
+        return Vec::new();
+    };
+
+    let ids = ids_on_line(
+        tree.root_node(),
+        source.as_bytes(),
+        diagnostic.range.start.line,
+        name,
+    );
+    if ids.is_empty() {
+        return Vec::new();
+    }
+
+    let explicit = db.explicit_decls_recursive(Arc::clone(uri));
+    let implicit = db.implicit_decls();
+
+    explicit
+        .iter()
+        .chain(implicit.iter())
+        .filter(|d| d.id == name && d.is_export != Some(false))
+        .filter_map(|d| d.fqid.strip_suffix(&format!("::{name}")))
+        .unique()
+        .sorted()
+        .map(|module| Fix {
+            title: format!("Qualify with module `{module}::`"),
+            edits: ids
+                .iter()
+                .map(|id| TextEdit::new(id.range(), format!("{module}::{name}")))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Turn an assignment in a condition into a comparison.
+fn replace_assignment_in_condition(
+    db: &Database,
+    uri: &Arc<Url>,
+    diagnostic: &Diagnostic,
+) -> Vec<Fix> {
+    if !Rule::AssignmentInCondition.matches(diagnostic) {
+        return Vec::new();
+    }
+
+    let Some(tree) = db.parse(Arc::clone(uri)) else {
+        return Vec::new();
+    };
+
+    tree.root_node()
+        .descendant_for_position(diagnostic.range.start)
+        .filter(|op| op.kind() == "=" && op.range() == diagnostic.range)
+        .map(|op| {
+            Fix::new(
+                "Replace `=` with `==` in condition",
+                TextEdit::new(op.range(), "==".into()),
+            )
+        })
+        .into_iter()
+        .collect()
+}
+
+/// Mark a field added with `redef record` as `&optional`.
+fn add_optional(db: &Database, uri: &Arc<Url>, diagnostic: &Diagnostic) -> Vec<Fix> {
+    if !Rule::RedefFieldNotOptional.matches(diagnostic) {
+        return Vec::new();
+    }
+
+    let Some(tree) = db.parse(Arc::clone(uri)) else {
+        return Vec::new();
+    };
+
+    let Some(spec) = tree
+        .root_node()
+        .named_descendant_for_point_range(diagnostic.range)
+        .and_then(|id| id.parent())
+        .filter(|p| p.kind() == "type_spec")
+    else {
+        return Vec::new();
+    };
+
+    // Attributes follow the type, so append to them if there are any.
+    let Some(last) = spec
+        .named_child("attr_list")
+        .or_else(|| spec.named_children("attr").last().copied())
+        .or_else(|| spec.named_child("type"))
+    else {
+        return Vec::new();
+    };
+
+    let end = last.range().end;
+    vec![Fix::new(
+        "Add `&optional`",
+        TextEdit::new(Range::new(end, end), " &optional".into()),
+    )]
+}
+
+/// The range to remove to delete the text in `range`.
+///
+/// If nothing else is on the lines of `range` the whole lines are removed.
+pub(crate) fn removal_range(source: &str, range: Range) -> Range {
+    let line = |n: u32| source.lines().nth(usize::try_from(n).ok()?);
+
+    let before = line(range.start.line)
+        .and_then(|l| l.get(..usize::try_from(range.start.character).ok()?))
+        .unwrap_or_default();
+    let after = line(range.end.line)
+        .and_then(|l| l.get(usize::try_from(range.end.character).ok()?..))
+        .unwrap_or_default();
+
+    if before.trim().is_empty() && after.trim().is_empty() {
+        Range::new(
+            Position::new(range.start.line, 0),
+            Position::new(range.end.line + 1, 0),
+        )
+    } else {
+        range
+    }
+}
+
+/// Remove a `@load` which has no effect, or whose declarations are not used.
+fn remove_load(db: &Database, uri: &Arc<Url>, diagnostic: &Diagnostic) -> Vec<Fix> {
+    let title = if Rule::RedundantLoad.matches(diagnostic) {
+        "Remove redundant load"
+    } else if Rule::UnusedLoad.matches(diagnostic) {
+        "Remove unused load"
+    } else {
+        return Vec::new();
+    };
+
+    let Some(source) = db.source(Arc::clone(uri)) else {
+        return Vec::new();
+    };
+
+    let range = removal_range(&source, diagnostic.range);
+
+    vec![Fix::new(title, TextEdit::new(range, String::new()))]
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::sync::Arc;
+
+    use tower_lsp::lsp_types::{Diagnostic, Position, Range, TextEdit, Url};
+
+    use crate::{lint, lsp::TestDatabase, Files};
+
+    pub(crate) fn apply(source: &str, edits: &[TextEdit]) -> String {
+        let mut lines: Vec<String> = source.lines().map(String::from).collect();
+
+        // Apply edits back to front so earlier positions stay valid.
+        for edit in edits.iter().rev() {
+            let Range { start, end } = edit.range;
+            let (start_line, end_line) = (start.line as usize, end.line as usize);
+
+            let prefix = &lines[start_line][..start.character as usize];
+            let suffix = lines
+                .get(end_line)
+                .map(|l| l[end.character as usize..].to_string());
+
+            let replaced = match suffix {
+                Some(suffix) => format!("{prefix}{}{suffix}", edit.new_text),
+                None => format!("{prefix}{}", edit.new_text),
+            };
+
+            let end_line = end_line.min(lines.len() - 1);
+            lines.splice(start_line..=end_line, replaced.lines().map(String::from));
+        }
+
+        lines.join("\n")
+    }
+
+    fn fixes(db: &TestDatabase, uri: &Arc<Url>, diag: &Diagnostic) -> Vec<(String, String)> {
+        let source = db.0.source(Arc::clone(uri)).unwrap();
+
+        super::fixes(&db.0, uri, diag)
+            .into_iter()
+            .map(|fix| (fix.title, apply(&source, &fix.edits)))
+            .collect()
+    }
+
+    fn zeek_error(line: u32, message: &str) -> Diagnostic {
+        let position = Position::new(line, 0);
+        Diagnostic {
+            source: Some("zeek".into()),
+            ..Diagnostic::new_simple(Range::new(position, position), message.into())
+        }
+    }
+
+    #[test]
+    fn declare_local() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), "event e() {\n    x = 1;\n}");
+
+        assert_eq!(
+            fixes(
+                &db,
+                &uri,
+                &zeek_error(1, "unknown identifier x, at or near \"x\"")
+            ),
+            [(
+                "Declare 'x' as local".to_string(),
+                "event e() {\n    local x = 1;\n}".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn qualify_with_module() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            "module M;\nexport { global x = 1; }\nmodule GLOBAL;\nprint x + x;",
+        );
+
+        assert_eq!(
+            fixes(
+                &db,
+                &uri,
+                &zeek_error(3, "unknown identifier x, at or near \"x\"")
+            ),
+            [(
+                "Qualify with module `M::`".to_string(),
+                "module M;\nexport { global x = 1; }\nmodule GLOBAL;\nprint M::x + M::x;"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn assignment_in_condition() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            "global x = 1;\nevent e() {\n    if ( x = 1 )\n        print x;\n}",
+        );
+
+        let diags = lint::diagnostics(&db.0, Arc::clone(&uri));
+        let diag = diags
+            .iter()
+            .find(|d| lint::Rule::AssignmentInCondition.matches(d))
+            .unwrap();
+
+        assert_eq!(
+            fixes(&db, &uri, diag),
+            [(
+                "Replace `=` with `==` in condition".to_string(),
+                "global x = 1;\nevent e() {\n    if ( x == 1 )\n        print x;\n}".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn add_optional() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            "type R: record { a: count; };\nredef record R += { b: count &log; };",
+        );
+
+        let diags = lint::diagnostics(&db.0, Arc::clone(&uri));
+        let diag = diags
+            .iter()
+            .find(|d| lint::Rule::RedefFieldNotOptional.matches(d))
+            .unwrap();
+
+        assert_eq!(
+            fixes(&db, &uri, diag),
+            [(
+                "Add `&optional`".to_string(),
+                "type R: record { a: count; };\nredef record R += { b: count &log &optional; };"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn remove_redundant_load() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), "@load ./a\n@load ./b\n@load ./a\nprint 1;");
+        db.add_file(Url::from_file_path("/a.zeek").unwrap(), "");
+        db.add_file(Url::from_file_path("/b.zeek").unwrap(), "");
+
+        let diags = lint::diagnostics(&db.0, Arc::clone(&uri));
+        let diag = diags
+            .iter()
+            .find(|d| lint::Rule::RedundantLoad.matches(d))
+            .unwrap();
+
+        assert_eq!(
+            fixes(&db, &uri, diag),
+            [(
+                "Remove redundant load".to_string(),
+                "@load ./a\n@load ./b\nprint 1;".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn remove_unused_load() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), "@load ./a\n@load ./b\n@load ./c\nprint A;");
+        db.add_file(Url::from_file_path("/a.zeek").unwrap(), "global A = 1;");
+        db.add_file(Url::from_file_path("/b.zeek").unwrap(), "global B = 2;");
+        db.add_file(
+            Url::from_file_path("/c.zeek").unwrap(),
+            "global C = 3;\nevent zeek_init() { print C; }",
+        );
+
+        let diags = lint::diagnostics(&db.0, Arc::clone(&uri));
+        let unused: Vec<_> = diags
+            .iter()
+            .filter(|d| lint::Rule::UnusedLoad.matches(d))
+            .collect();
+
+        // Scripts with handlers have effects even if their declarations are unused.
+        assert_eq!(unused.len(), 1);
+        assert_eq!(
+            fixes(&db, &uri, unused[0]),
+            [(
+                "Remove unused load".to_string(),
+                "@load ./a\n@load ./c\nprint A;".to_string()
+            )]
+        );
+    }
+}
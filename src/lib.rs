@@ -3,12 +3,23 @@ use tower_lsp::lsp_types::{ClientCapabilities, Url};
 use tracing::instrument;
 
 pub mod ast;
+pub mod btest;
 pub mod complete;
+pub mod fix;
+pub mod lint;
+pub mod logs;
 pub mod lsp;
 pub mod parse;
+pub mod preproc;
 pub mod query;
+pub mod refactor;
 pub mod rst;
+pub mod sig;
+pub mod syntax;
+pub mod text;
+pub mod typecheck;
 pub mod zeek;
+pub mod zkg;
 
 #[allow(clippy::trait_duplication_in_bounds)]
 #[salsa::query_group(FilesStorage)]
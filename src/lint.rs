@@ -0,0 +1,1744 @@
+//! Lints for common mistakes in Zeek scripts.
+//!
+//! Each [`Lint`] checks a single [`Rule`]. Rules have stable codes which are attached to their
+//! diagnostics, and their severity can be configured per rule with the `lints` initialization
+//! option. Diagnostics can be suppressed in source with a comment like
+//! `# zeek-lsp: ignore[unused-local]`.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+};
+
+use itertools::Itertools;
+use regex::Regex;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Deserialize;
+use tower_lsp::lsp_types::{
+    CodeDescription, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag,
+    Location, NumberOrString, Range, TextEdit, Url,
+};
+use tracing::instrument;
+
+use crate::{
+    ast::{self, Ast},
+    lsp::Database,
+    parse::Parse,
+    query::{self, Decl, DeclKind, Node, NodeLocation, Query},
+    typecheck, Client, Files,
+};
+
+/// A lint rule with a stable code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    OptionalFieldAccess,
+    UnusedLocal,
+    UnusedParameter,
+    ShadowedDeclaration,
+    ConflictingGlobal,
+    DuplicateFunction,
+    NonExhaustiveSwitch,
+    AssignmentInCondition,
+    RedundantLoad,
+    UnusedLoad,
+    RedefFieldNotOptional,
+    TypeMismatch,
+    HandlerSignature,
+    Deprecated,
+    InactiveCode,
+    RequiresDefaultScripts,
+}
+
+impl Rule {
+    pub const ALL: [Self; 16] = [
+        Self::OptionalFieldAccess,
+        Self::UnusedLocal,
+        Self::UnusedParameter,
+        Self::ShadowedDeclaration,
+        Self::ConflictingGlobal,
+        Self::DuplicateFunction,
+        Self::NonExhaustiveSwitch,
+        Self::AssignmentInCondition,
+        Self::RedundantLoad,
+        Self::UnusedLoad,
+        Self::RedefFieldNotOptional,
+        Self::TypeMismatch,
+        Self::HandlerSignature,
+        Self::Deprecated,
+        Self::InactiveCode,
+        Self::RequiresDefaultScripts,
+    ];
+
+    /// The code of the rule used in diagnostics, configuration and suppressions.
+    #[must_use]
+    pub const fn code(self) -> &'static str {
+        match self {
+            Self::OptionalFieldAccess => "optional-field-access",
+            Self::UnusedLocal => "unused-local",
+            Self::UnusedParameter => "unused-parameter",
+            Self::ShadowedDeclaration => "shadowed-declaration",
+            Self::ConflictingGlobal => "conflicting-global",
+            Self::DuplicateFunction => "duplicate-function",
+            Self::NonExhaustiveSwitch => "non-exhaustive-switch",
+            Self::AssignmentInCondition => "assignment-in-condition",
+            Self::RedundantLoad => "redundant-load",
+            Self::UnusedLoad => "unused-load",
+            Self::RedefFieldNotOptional => "redef-field-not-optional",
+            Self::TypeMismatch => "type-mismatch",
+            Self::HandlerSignature => "handler-signature",
+            Self::Deprecated => "deprecated",
+            Self::InactiveCode => "inactive-code",
+            Self::RequiresDefaultScripts => "requires-default-scripts",
+        }
+    }
+
+    /// Looks up a rule by its code.
+    #[must_use]
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.code() == code)
+    }
+
+    /// Severity of diagnostics of this rule if not configured otherwise.
+    #[must_use]
+    pub const fn default_level(self) -> Level {
+        match self {
+            Self::DuplicateFunction
+            | Self::RedefFieldNotOptional
+            | Self::TypeMismatch
+            | Self::HandlerSignature => Level::Error,
+            Self::InactiveCode => Level::Hint,
+            _ => Level::Warning,
+        }
+    }
+
+    /// Whether the diagnostic was reported for this rule.
+    pub(crate) fn matches(self, diagnostic: &Diagnostic) -> bool {
+        diagnostic.code == Some(NumberOrString::String(self.code().into()))
+    }
+
+    /// Link to the documentation of the rule.
+    fn code_description(self) -> Option<CodeDescription> {
+        let href = format!("{}#{}", env!("CARGO_PKG_REPOSITORY"), self.code());
+        Some(CodeDescription {
+            href: Url::parse(&href).ok()?,
+        })
+    }
+}
+
+/// Configured level of a rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Off,
+    Hint,
+    Information,
+    Warning,
+    Error,
+}
+
+impl Level {
+    const fn severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            Self::Off => None,
+            Self::Hint => Some(DiagnosticSeverity::HINT),
+            Self::Information => Some(DiagnosticSeverity::INFORMATION),
+            Self::Warning => Some(DiagnosticSeverity::WARNING),
+            Self::Error => Some(DiagnosticSeverity::ERROR),
+        }
+    }
+}
+
+/// A check for a single [`Rule`].
+pub trait Lint: Sync {
+    /// The rule checked by this lint.
+    fn rule(&self) -> Rule;
+
+    /// Computes diagnostics for the given file.
+    ///
+    /// The code and severity of the returned diagnostics are filled in from the rule.
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic>;
+}
+
+/// All known lints.
+static LINTS: &[&dyn Lint] = &[
+    &OptionalFieldAccess,
+    &UnusedLocal,
+    &UnusedParameter,
+    &ShadowedDeclaration,
+    &ConflictingGlobal,
+    &DuplicateFunction,
+    &typecheck::NonExhaustiveSwitch,
+    &AssignmentInCondition,
+    &RedundantLoad,
+    &UnusedLoad,
+    &typecheck::RedefFieldNotOptional,
+    &typecheck::TypeMismatch,
+    &typecheck::HandlerSignature,
+    &Deprecated,
+    &InactiveCode,
+    &RequiresDefaultScripts,
+];
+
+/// Computes lint diagnostics for the given file.
+///
+/// Rules are configured with the `lints` option in the database's initialization options.
+#[allow(clippy::needless_pass_by_value)]
+#[instrument(skip(db))]
+pub fn diagnostics(db: &Database, uri: Arc<Url>) -> Vec<Diagnostic> {
+    let Some(source) = db.source(Arc::clone(&uri)) else {
+        return Vec::new();
+    };
+
+    let Some(tree) = db.parse(Arc::clone(&uri)) else {
+        return Vec::new();
+    };
+
+    let options = db.initialization_options();
+    let suppressions = Suppressions::parse(tree.root_node(), &source);
+
+    LINTS
+        .iter()
+        .flat_map(|lint| {
+            let rule = lint.rule();
+
+            let level = options
+                .lints
+                .get(rule.code())
+                .copied()
+                .unwrap_or_else(|| rule.default_level());
+            let Some(severity) = level.severity() else {
+                return Vec::new();
+            };
+
+            lint.check(db, &uri)
+                .into_iter()
+                .filter(|d| !suppressions.is_suppressed(rule, d.range.start.line))
+                .map(|d| Diagnostic {
+                    severity: Some(severity),
+                    code: Some(NumberOrString::String(rule.code().into())),
+                    code_description: rule.code_description(),
+                    ..d
+                })
+                .collect()
+        })
+        .sorted_by_key(|d| (d.range.start.line, d.range.start.character))
+        .collect()
+}
+
+/// Checks scripts outside of a language server session.
+///
+/// # Arguments
+///
+/// * `paths` - scripts, or directories containing scripts, to check
+pub async fn check(paths: &[PathBuf]) -> Vec<(Arc<Url>, Diagnostic)> {
+    let (db, roots) = Database::from_scripts(paths).await;
+
+    roots
+        .into_iter()
+        .flat_map(|uri| {
+            diagnostics(&db, Arc::clone(&uri))
+                .into_iter()
+                .map(move |d| (Arc::clone(&uri), d))
+        })
+        .collect()
+}
+
+/// Renders diagnostics in the format `path:line:column: severity[code]: message`.
+#[must_use]
+pub fn render(diagnostics: &[(Arc<Url>, Diagnostic)]) -> String {
+    diagnostics
+        .iter()
+        .map(|(uri, d)| {
+            let path = uri
+                .to_file_path()
+                .map_or_else(|()| uri.to_string(), |p| p.display().to_string());
+
+            let severity = match d.severity {
+                Some(DiagnosticSeverity::ERROR) => "error",
+                Some(DiagnosticSeverity::INFORMATION) => "info",
+                Some(DiagnosticSeverity::HINT) => "hint",
+                _ => "warning",
+            };
+
+            let code = match &d.code {
+                Some(NumberOrString::String(code)) => code.clone(),
+                Some(NumberOrString::Number(code)) => code.to_string(),
+                None => String::new(),
+            };
+
+            format!(
+                "{path}:{}:{}: {severity}[{code}]: {}",
+                d.range.start.line + 1,
+                d.range.start.character + 1,
+                d.message
+            )
+        })
+        .join("\n")
+}
+
+/// Rules suppressed with `# zeek-lsp: ignore[rule, ...]` comments, by line.
+///
+/// A suppression applies to the line of the comment, and if the comment is on a line of its own
+/// also to the following line. Without a list of rules all rules are suppressed.
+struct Suppressions<'a>(FxHashMap<u32, Vec<&'a str>>);
+
+impl<'a> Suppressions<'a> {
+    /// Collect suppressions from the comments under `root`.
+    fn parse(root: Node, source: &'a str) -> Self {
+        static SUPPRESSION: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^#+\s*zeek-lsp:\s*ignore(?:\[([^\]]*)\])?").expect("invalid regex")
+        });
+
+        fn collect_comments<'t>(node: Node<'t>, comments: &mut Vec<Node<'t>>) {
+            if node.kind().ends_with("comment") {
+                comments.push(node);
+                return;
+            }
+
+            for c in node.named_children_not("nl") {
+                collect_comments(c, comments);
+            }
+        }
+
+        let mut nodes = Vec::new();
+        collect_comments(root, &mut nodes);
+
+        let mut suppressions: FxHashMap<u32, Vec<&str>> = FxHashMap::default();
+
+        for comment in nodes {
+            let Some(m) = comment
+                .utf8_text(source.as_bytes())
+                .ok()
+                .and_then(|text| SUPPRESSION.captures(text))
+            else {
+                continue;
+            };
+            let start = comment.range().start;
+            let line = start.line;
+
+            let rules: Vec<_> = m
+                .get(1)
+                .map(|rules| {
+                    rules
+                        .as_str()
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|r| !r.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            let own_line = source
+                .lines()
+                .nth(line as usize)
+                .and_then(|l| l.get(..start.character as usize))
+                .is_some_and(|before| before.trim().is_empty());
+            for line in [Some(line), own_line.then_some(line + 1)]
+                .into_iter()
+                .flatten()
+            {
+                // An empty list suppresses all rules.
+                if let Some(suppressed) = suppressions.get_mut(&line) {
+                    if rules.is_empty() {
+                        suppressed.clear();
+                    } else if !suppressed.is_empty() {
+                        suppressed.extend(&rules);
+                    }
+                } else {
+                    suppressions.insert(line, rules.clone());
+                }
+            }
+        }
+
+        Self(suppressions)
+    }
+
+    fn is_suppressed(&self, rule: Rule, line: u32) -> bool {
+        self.0
+            .get(&line)
+            .is_some_and(|rules| rules.is_empty() || rules.contains(&rule.code()))
+    }
+}
+
+/// Reads of `&optional` fields without checking whether they are set.
+struct OptionalFieldAccess;
+
+impl Lint for OptionalFieldAccess {
+    fn rule(&self) -> Rule {
+        Rule::OptionalFieldAccess
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        let (Some(tree), Some(source)) = (db.parse(Arc::clone(uri)), db.source(Arc::clone(uri)))
+        else {
+            return Vec::new();
+        };
+
+        let mut diags = Vec::new();
+        visit(db, uri, source.as_bytes(), tree.root_node(), &mut diags);
+        diags
+    }
+}
+
+/// Locals which are never read.
+struct UnusedLocal;
+
+impl Lint for UnusedLocal {
+    fn rule(&self) -> Rule {
+        Rule::UnusedLocal
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_unused(db, uri, BindingKind::Local)
+    }
+}
+
+/// Function parameters which are never read.
+struct UnusedParameter;
+
+impl Lint for UnusedParameter {
+    fn rule(&self) -> Rule {
+        Rule::UnusedParameter
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_unused(db, uri, BindingKind::Parameter)
+    }
+}
+
+/// Locals shadowing a parameter or a global.
+struct ShadowedDeclaration;
+
+impl Lint for ShadowedDeclaration {
+    fn rule(&self) -> Rule {
+        Rule::ShadowedDeclaration
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_shadowing(db, uri)
+    }
+}
+
+/// Globals declared with different types.
+struct ConflictingGlobal;
+
+impl Lint for ConflictingGlobal {
+    fn rule(&self) -> Rule {
+        Rule::ConflictingGlobal
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_redeclarations(db, uri, false)
+    }
+}
+
+/// Functions defined more than once.
+struct DuplicateFunction;
+
+impl Lint for DuplicateFunction {
+    fn rule(&self) -> Rule {
+        Rule::DuplicateFunction
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_redeclarations(db, uri, true)
+    }
+}
+
+/// Assignments used as conditions of `if` or `while` statements.
+struct AssignmentInCondition;
+
+impl Lint for AssignmentInCondition {
+    fn rule(&self) -> Rule {
+        Rule::AssignmentInCondition
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        let Some(tree) = db.parse(Arc::clone(uri)) else {
+            return Vec::new();
+        };
+
+        let mut diags = Vec::new();
+        check_conditions(tree.root_node(), &mut diags);
+        diags
+    }
+}
+
+/// Loads of scripts which are already loaded by an earlier `@load`.
+struct RedundantLoad;
+
+impl Lint for RedundantLoad {
+    fn rule(&self) -> Rule {
+        Rule::RedundantLoad
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_loads(db, uri)
+    }
+}
+
+/// Loads of scripts none of whose declarations are used.
+struct UnusedLoad;
+
+impl Lint for UnusedLoad {
+    fn rule(&self) -> Rule {
+        Rule::UnusedLoad
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_unused_loads(db, uri)
+    }
+}
+
+/// Inactive branches of conditional directives, marked as unnecessary code.
+struct InactiveCode;
+
+impl Lint for InactiveCode {
+    fn rule(&self) -> Rule {
+        Rule::InactiveCode
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        db.inactive_ranges(Arc::clone(uri))
+            .iter()
+            .map(|r| Diagnostic {
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                ..Diagnostic::new_simple(*r, "inactive conditional branch".into())
+            })
+            .collect()
+    }
+}
+
+/// In bare mode, identifiers which could only be resolved if Zeek loaded its default scripts.
+struct RequiresDefaultScripts;
+
+impl Lint for RequiresDefaultScripts {
+    fn rule(&self) -> Rule {
+        Rule::RequiresDefaultScripts
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_bare_mode(db, uri)
+    }
+}
+
+/// Uses of identifiers marked `&deprecated`.
+struct Deprecated;
+
+impl Lint for Deprecated {
+    fn rule(&self) -> Rule {
+        Rule::Deprecated
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_deprecations(db, uri)
+    }
+}
+
+fn visit(db: &Database, uri: &Arc<Url>, source: &[u8], node: Node, diags: &mut Vec<Diagnostic>) {
+    // Do not attempt to make sense of code with syntax errors.
+    if node.kind() == "ERROR" {
+        return;
+    }
+
+    if node.kind() == "field_access" {
+        diags.extend(check_optional_access(db, uri, source, node));
+    }
+
+    for c in node.named_children_not("nl") {
+        visit(db, uri, source, c, diags);
+    }
+}
+
+fn check_conditions(node: Node, diags: &mut Vec<Diagnostic>) {
+    if node.kind() == "ERROR" {
+        return;
+    }
+
+    if let [kw, open, cond, close, ..] = node.children().as_slice() {
+        if matches!(kw.kind(), "if" | "while")
+            && open.kind() == "("
+            && close.kind() == ")"
+            && typecheck::is_assignment(*cond)
+        {
+            if let Some(op) = cond.children().get(1) {
+                diags.push(Diagnostic::new_simple(
+                    op.range(),
+                    "assignment used as condition, did you mean '=='?".into(),
+                ));
+            }
+        }
+    }
+
+    for c in node.named_children_not("nl") {
+        check_conditions(c, diags);
+    }
+}
+
+/// Collects all `@load`s in the tree with the node holding the loaded file.
+fn load_nodes<'a>(node: Node<'a>, loads: &mut Vec<(Node<'a>, Node<'a>)>) {
+    let children = node.children();
+
+    for (load, file) in children.iter().tuple_windows() {
+        if load.kind() == "@load" && file.kind() == "file" {
+            loads.push((*load, *file));
+        }
+    }
+
+    for c in children.into_iter().filter(Node::is_named) {
+        load_nodes(c, loads);
+    }
+}
+
+/// Collects all `@load`s in the file together with the node holding the loaded file, and the file
+/// it resolves to.
+fn resolved_loads<'a>(
+    db: &Database,
+    uri: &Arc<Url>,
+    root: Node<'a>,
+    source: &str,
+) -> Vec<(Node<'a>, Node<'a>, Arc<Url>)> {
+    let files = db.files();
+    let prefixes = db.prefixes();
+    let packages = db.packages();
+
+    let mut loads = Vec::new();
+    load_nodes(root, &mut loads);
+
+    loads
+        .into_iter()
+        .filter_map(|(load, file)| {
+            let name = file.utf8_text(source.as_bytes()).ok()?;
+            let f = ast::load_to_file(Path::new(name), uri, &files, &prefixes, &packages)?;
+            Some((load, file, f))
+        })
+        .collect()
+}
+
+fn check_loads(db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+    let (Some(tree), Some(source)) = (db.parse(Arc::clone(uri)), db.source(Arc::clone(uri))) else {
+        return Vec::new();
+    };
+
+    // Files loaded so far, together with the load which pulled them in.
+    let mut loaded: Vec<(Arc<Url>, Node)> = Vec::new();
+    let mut diags = Vec::new();
+
+    for (load, file, f) in resolved_loads(db, uri, tree.root_node(), &source) {
+        let name = file.utf8_text(source.as_bytes()).unwrap_or_default();
+        let range = Range::new(load.range().start, file.range().end);
+
+        if let Some((_, earlier)) = loaded.iter().find(|(l, _)| l == &f) {
+            diags.push(Diagnostic {
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                ..Diagnostic::new_simple(
+                    range,
+                    format!(
+                        "'{name}' is already loaded by '@load {}' on line {}",
+                        earlier.utf8_text(source.as_bytes()).unwrap_or_default(),
+                        earlier.range().start.line + 1
+                    ),
+                )
+            });
+            continue;
+        }
+
+        loaded.extend(
+            std::iter::once(Arc::clone(&f))
+                .chain(db.loaded_files_recursive(f).iter().cloned())
+                .map(|l| (l, file)),
+        );
+    }
+
+    diags
+}
+
+/// Warn about loads of scripts whose declarations are not used in the file.
+///
+/// Scripts which define handlers or redefine declarations have effects even if nothing they
+/// declare is used, so loads of them are never reported.
+fn check_unused_loads(db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+    let (Some(tree), Some(source)) = (db.parse(Arc::clone(uri)), db.source(Arc::clone(uri))) else {
+        return Vec::new();
+    };
+
+    // Files declaring something used in the file.
+    let used: FxHashSet<_> = db
+        .ids(Arc::clone(uri))
+        .iter()
+        .filter_map(|id| {
+            db.resolve(id.clone())?
+                .loc
+                .as_ref()
+                .map(|l| Arc::clone(&l.uri))
+        })
+        .collect();
+
+    let mut seen = FxHashSet::default();
+    let mut diags = Vec::new();
+
+    for (load, file, f) in resolved_loads(db, uri, tree.root_node(), &source) {
+        // Repeated loads are reported as redundant instead.
+        if !seen.insert(Arc::clone(&f)) {
+            continue;
+        }
+
+        let provided: Vec<_> = std::iter::once(Arc::clone(&f))
+            .chain(db.loaded_files_recursive(f).iter().cloned())
+            .collect();
+
+        if provided.iter().any(|p| used.contains(p)) {
+            continue;
+        }
+
+        let decls: Vec<_> = provided
+            .iter()
+            .flat_map(|p| db.decls(Arc::clone(p)).iter().cloned().collect::<Vec<_>>())
+            .collect();
+        let has_effects = decls.iter().any(|d| {
+            matches!(
+                d.kind,
+                DeclKind::Redef
+                    | DeclKind::RedefRecord(_)
+                    | DeclKind::RedefEnum(_)
+                    | DeclKind::EventDef(_)
+                    | DeclKind::HookDef(_)
+            )
+        });
+        if decls.is_empty() || has_effects {
+            continue;
+        }
+
+        diags.push(Diagnostic {
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            ..Diagnostic::new_simple(
+                Range::new(load.range().start, file.range().end),
+                format!(
+                    "nothing declared by '{}' is used",
+                    file.utf8_text(source.as_bytes()).unwrap_or_default()
+                ),
+            )
+        });
+    }
+
+    diags
+}
+
+/// In bare mode, flags identifiers which could only be resolved if Zeek loaded its default scripts.
+fn check_bare_mode(db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+    if !db.bare_mode() {
+        return Vec::new();
+    }
+
+    let (Some(tree), Some(source)) = (db.parse(Arc::clone(uri)), db.source(Arc::clone(uri))) else {
+        return Vec::new();
+    };
+
+    let default_decls = db.default_decls();
+    if default_decls.is_empty() {
+        return Vec::new();
+    }
+
+    let dir = uri
+        .to_file_path()
+        .ok()
+        .and_then(|f| f.parent().map(PathBuf::from));
+    let prefixes = db.prefixes();
+
+    db.ids(Arc::clone(uri))
+        .iter()
+        .filter_map(|id| {
+            if db.resolve(id.clone()).is_some() {
+                return None;
+            }
+
+            let text = tree
+                .root_node()
+                .named_descendant_for_point_range(id.range)?
+                .utf8_text(source.as_bytes())
+                .ok()?;
+
+            let decl = default_decls.iter().find(|d| d.fqid == text)?;
+
+            let load = decl.loc.as_ref().and_then(|loc| {
+                ast::file_to_load(&loc.uri, dir.as_ref()?, &prefixes)
+            });
+
+            let message = if let Some(load) = load {
+                format!("'{text}' is only available when Zeek loads its default scripts, add '@load {load}'")
+            } else {
+                format!("'{text}' is only available when Zeek loads its default scripts")
+            };
+
+            Some(Diagnostic::new_simple(id.range, message))
+        })
+        .collect()
+}
+
+/// Warns about uses of identifiers marked `&deprecated`.
+fn check_deprecations(db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+    db.ids(Arc::clone(uri))
+        .iter()
+        .filter_map(|id| {
+            let decl = db.resolve(id.clone())?;
+            let message = decl.deprecation()?;
+
+            // Do not flag the declaration itself.
+            if let Some(loc) = &decl.loc {
+                if loc.uri == id.uri
+                    && loc.range.start <= id.range.start
+                    && id.range.end <= loc.range.end
+                {
+                    return None;
+                }
+            }
+
+            let message = if message.is_empty() {
+                format!("'{}' is deprecated", decl.fqid)
+            } else {
+                format!("'{}' is deprecated: {message}", decl.fqid)
+            };
+
+            Some(Diagnostic {
+                tags: Some(vec![DiagnosticTag::DEPRECATED]),
+                ..Diagnostic::new_simple(id.range, message)
+            })
+        })
+        .collect()
+}
+
+/// A local variable or function parameter which is expected to be read.
+struct Binding {
+    /// Name of the binding.
+    id: String,
+
+    /// Range of the declaring identifier.
+    range: Range,
+
+    kind: BindingKind,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BindingKind {
+    Local,
+    Parameter,
+}
+
+impl BindingKind {
+    /// Human-readable name of the kind, e.g., `local variable`.
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Local => "local variable",
+            Self::Parameter => "parameter",
+        }
+    }
+}
+
+/// Collects declarations of the given kind in the file.
+fn bindings(
+    db: &Database,
+    uri: &Arc<Url>,
+    source: &[u8],
+    root: Node,
+    kind: BindingKind,
+) -> Vec<Binding> {
+    let mut bindings = Vec::new();
+    collect_bindings(db, uri, source, root, &mut bindings);
+    bindings.retain(|b| b.kind == kind);
+    bindings
+}
+
+/// Collects declarations of locals and function parameters.
+fn collect_bindings(
+    db: &Database,
+    uri: &Arc<Url>,
+    source: &[u8],
+    node: Node,
+    bindings: &mut Vec<Binding>,
+) {
+    if node.kind() == "ERROR" {
+        return;
+    }
+
+    match node.kind() {
+        "var_decl" if node.children().first().is_some_and(|c| c.kind() == "local") => {
+            if let Some(id) = node.named_child("id") {
+                bindings.extend(id.utf8_text(source).ok().map(|name| Binding {
+                    id: name.into(),
+                    range: id.range(),
+                    kind: BindingKind::Local,
+                }));
+            }
+        }
+        // Handlers often need to accept parameters they do not use since their signature is fixed
+        // by the event or hook declaration.
+        "func_decl" | "hook_decl" | "event_decl"
+            if node.kind() == "func_decl"
+                || db.initialization_options().unused_handler_parameters =>
+        {
+            bindings.extend(
+                query::fn_param_decls(node, Arc::clone(uri), source)
+                    .into_iter()
+                    .filter_map(|d| {
+                        Some(Binding {
+                            id: d.id.to_string(),
+                            range: d.loc?.range,
+                            kind: BindingKind::Parameter,
+                        })
+                    }),
+            );
+        }
+        _ => {}
+    }
+
+    for c in node.named_children_not("nl") {
+        collect_bindings(db, uri, source, c, bindings);
+    }
+}
+
+/// Warn about bindings of the given kind which are never read.
+fn check_unused(db: &Database, uri: &Arc<Url>, kind: BindingKind) -> Vec<Diagnostic> {
+    let (Some(tree), Some(source)) = (db.parse(Arc::clone(uri)), db.source(Arc::clone(uri))) else {
+        return Vec::new();
+    };
+    let (root, source) = (tree.root_node(), source.as_bytes());
+
+    // Bindings prefixed with `_` are unused on purpose.
+    let bindings: Vec<_> = bindings(db, uri, source, root, kind)
+        .into_iter()
+        .filter(|b| !b.id.starts_with('_'))
+        .collect();
+    if bindings.is_empty() {
+        return Vec::new();
+    }
+
+    let names: FxHashSet<_> = bindings.iter().map(|b| b.id.as_str()).collect();
+    let decls: Vec<_> = bindings.iter().map(|b| b.range).collect();
+
+    // The declarations each binding and read identifier resolve to. We resolve by name instead of
+    // with `Ast::resolve` since the latter resolves a record in a field access to the field.
+    let resolve = |id: &str, range: Range| {
+        db.resolve_id(id.into(), NodeLocation::from_range(Arc::clone(uri), range))
+            .and_then(|d| d.loc.as_ref().map(|l| l.range))
+    };
+
+    let reads: Vec<_> = db
+        .ids(Arc::clone(uri))
+        .iter()
+        .filter(|id| !decls.contains(&id.range))
+        .filter_map(|id| {
+            let node = root.named_descendant_for_point_range(id.range)?;
+            let name = node.utf8_text(source).ok()?;
+            if !names.contains(name) || is_write(node) || is_field_name(node) {
+                return None;
+            }
+            resolve(name, id.range)
+        })
+        .collect();
+
+    bindings
+        .into_iter()
+        .filter(|b| resolve(&b.id, b.range).is_some_and(|d| !reads.contains(&d)))
+        .map(|b| Diagnostic {
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            ..Diagnostic::new_simple(b.range, format!("{} '{}' is never used", kind.name(), b.id))
+        })
+        .collect()
+}
+
+/// Warn about locals which shadow a parameter or a global.
+fn check_shadowing(db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+    let (Some(tree), Some(source)) = (db.parse(Arc::clone(uri)), db.source(Arc::clone(uri))) else {
+        return Vec::new();
+    };
+    let (root, source) = (tree.root_node(), source.as_bytes());
+
+    bindings(db, uri, source, root, BindingKind::Local)
+        .into_iter()
+        .filter_map(|b| {
+            let node = root.named_descendant_for_point_range(b.range)?;
+
+            let (shadowed, what) = if let Some(param) = shadowed_parameter(uri, source, node, &b.id)
+            {
+                (Arc::new(param), "parameter")
+            } else {
+                (shadowed_global(db, uri, source, root, &b.id)?, "global")
+            };
+            let loc = shadowed.loc.as_ref()?;
+
+            Some(Diagnostic {
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location::new((*loc.uri).clone(), loc.selection_range),
+                    message: format!("shadowed {what} is declared here"),
+                }]),
+                ..Diagnostic::new_simple(
+                    b.range,
+                    format!("local '{}' shadows {what} '{}'", b.id, shadowed.fqid),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Find a parameter of the function, event or hook containing the node with the given name.
+fn shadowed_parameter(uri: &Arc<Url>, source: &[u8], node: Node, id: &str) -> Option<Decl> {
+    let mut f = node.parent()?;
+    while !matches!(f.kind(), "func_decl" | "hook_decl" | "event_decl") {
+        f = f.parent()?;
+    }
+
+    query::fn_param_decls(f, Arc::clone(uri), source)
+        .into_iter()
+        .find(|d| &*d.id == id)
+}
+
+/// Find a global with the given name which would be visible if it was not shadowed.
+fn shadowed_global(
+    db: &Database,
+    uri: &Arc<Url>,
+    source: &[u8],
+    root: Node,
+    id: &str,
+) -> Option<Arc<Decl>> {
+    let module = root
+        .named_child("module_decl")
+        .and_then(|d| d.named_child("id"))
+        .and_then(|id| id.utf8_text(source).ok());
+
+    // Like `Ast::resolve` look for the plain name first, and in the current module second.
+    let scope = NodeLocation::from_node(Arc::clone(uri), root);
+    std::iter::once(id.to_string())
+        .chain(module.map(|m| format!("{m}::{id}")))
+        .filter_map(|fqid| db.resolve_id(fqid.as_str().into(), scope.clone()))
+        .find(|d| {
+            matches!(
+                d.kind,
+                DeclKind::Global
+                    | DeclKind::Const
+                    | DeclKind::Option
+                    | DeclKind::FuncDecl(_)
+                    | DeclKind::FuncDef(_)
+            )
+        })
+}
+
+/// Report globals redeclared with a different type, or with `functions` functions defined more
+/// than once.
+fn check_redeclarations(db: &Database, uri: &Arc<Url>, functions: bool) -> Vec<Diagnostic> {
+    let is_checked = |d: &Decl| {
+        if functions {
+            matches!(d.kind, DeclKind::FuncDef(_))
+        } else {
+            matches!(d.kind, DeclKind::Global)
+        }
+    };
+
+    let implicit_decls = db.implicit_decls();
+    let explicit_decls = db.explicit_decls_recursive(Arc::clone(uri));
+
+    let mut others: FxHashMap<&str, Vec<&Decl>> = FxHashMap::default();
+    for d in implicit_decls
+        .iter()
+        .chain(explicit_decls.iter())
+        .filter(|d| is_checked(d))
+    {
+        others.entry(&d.fqid).or_default().push(d);
+    }
+
+    db.decls(Arc::clone(uri))
+        .iter()
+        .filter(|d| is_checked(d) && ast::is_active(db, d))
+        .filter_map(|d| {
+            let loc = d.loc.as_ref()?;
+            let others = others
+                .get(&*d.fqid)?
+                .iter()
+                .filter(|o| o.loc.as_ref() != Some(loc));
+
+            let (message, other) = if functions {
+                let other = others.min_by_key(|o| o.loc.as_ref())?;
+                (
+                    format!("function '{}' is defined more than once", d.fqid),
+                    other,
+                )
+            } else {
+                let typ = declared_type(db, d)?;
+                let (other, other_typ) = others
+                    .filter_map(|o| Some((o, declared_type(db, o)?)))
+                    .filter(|(_, t)| *t != typ)
+                    .min_by_key(|(o, _)| o.loc.as_ref())?;
+                (
+                    format!(
+                        "global '{}' is declared with type '{typ}' here but with type \
+                        '{other_typ}' elsewhere",
+                        d.fqid
+                    ),
+                    other,
+                )
+            };
+
+            let other_loc = other.loc.as_ref()?;
+
+            Some(Diagnostic {
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location::new((*other_loc.uri).clone(), other_loc.selection_range),
+                    message: "other declaration".into(),
+                }]),
+                ..Diagnostic::new_simple(loc.selection_range, message)
+            })
+        })
+        .collect()
+}
+
+/// The type a variable was declared with, if it has an explicit type.
+fn declared_type(db: &Database, decl: &Decl) -> Option<String> {
+    let loc = decl.loc.as_ref()?;
+    let tree = db.parse(Arc::clone(&loc.uri))?;
+    let source = db.source(Arc::clone(&loc.uri))?;
+
+    let typ = tree
+        .root_node()
+        .named_descendant_for_point_range(loc.range)?
+        .named_child("type")?;
+
+    Some(
+        typ.utf8_text(source.as_bytes())
+            .ok()?
+            .split_whitespace()
+            .join(" "),
+    )
+}
+
+/// Whether the node is the field name in a field access or check, e.g., `uri` in `c$uri`.
+pub(crate) fn is_field_name(node: Node) -> bool {
+    node.parent().is_some_and(|p| {
+        matches!(p.kind(), "field_access" | "field_check")
+            && p.named_child("id")
+                .is_some_and(|f| f.range() == node.range())
+    })
+}
+
+/// A read of an `&optional` record field, e.g., `c$http`.
+struct OptionalAccess<'a> {
+    /// The record expression, e.g., `c`.
+    stem: Node<'a>,
+
+    /// Source of the record expression.
+    stem_text: &'a str,
+
+    /// Name of the field, e.g., `http`.
+    field: &'a str,
+}
+
+impl OptionalAccess<'_> {
+    /// The check whether the field is set, e.g., `c?$http`.
+    fn check(&self) -> String {
+        format!("{}?${}", self.stem_text.trim(), self.field)
+    }
+
+    /// Whether the node checks whether the field is set.
+    fn is_checked_by(&self, node: Node, source: &[u8]) -> bool {
+        node.kind() == "field_check"
+            && match node.children().as_slice() {
+                [stem, op, field] => {
+                    op.kind() == "?$"
+                        && field.utf8_text(source).is_ok_and(|f| f == self.field)
+                        && same_expr(*stem, self.stem, source)
+                }
+                _ => false,
+            }
+    }
+
+    /// Whether the node assigns the field, e.g., `c$http = ...`.
+    fn is_assigned_by(&self, node: Node, source: &[u8]) -> bool {
+        node.kind() == "field_access"
+            && is_write(node)
+            && node
+                .named_child("expr")
+                .is_some_and(|stem| same_expr(stem, self.stem, source))
+            && node
+                .named_child("id")
+                .and_then(|f| f.utf8_text(source).ok())
+                .is_some_and(|f| f == self.field)
+    }
+}
+
+/// If the node reads an `&optional` field without `&default` return the access.
+fn optional_access<'a>(
+    db: &Database,
+    uri: &Arc<Url>,
+    source: &'a [u8],
+    node: Node<'a>,
+) -> Option<OptionalAccess<'a>> {
+    let stem = node.named_child("expr")?;
+    let field = node.named_child("id")?;
+
+    if is_write(node) {
+        return None;
+    }
+
+    let decl = db.resolve(NodeLocation::from_node(Arc::clone(uri), node))?;
+    if !matches!(decl.kind, DeclKind::Field)
+        || !decl.attrs.iter().any(|a| a.name == "optional")
+        || decl.attrs.iter().any(|a| a.name == "default")
+    {
+        return None;
+    }
+
+    Some(OptionalAccess {
+        stem,
+        stem_text: stem.utf8_text(source).ok()?,
+        field: field.utf8_text(source).ok()?,
+    })
+}
+
+/// Warn about reads of `&optional` fields which are not guarded by a `?$` check.
+fn check_optional_access(
+    db: &Database,
+    uri: &Arc<Url>,
+    source: &[u8],
+    node: Node,
+) -> Option<Diagnostic> {
+    let access = optional_access(db, uri, source, node)?;
+    let check = access.check();
+
+    if is_guarded(node, &access, source) {
+        return None;
+    }
+
+    Some(Diagnostic::new_simple(
+        node.range(),
+        format!(
+            "optional field '{}' is accessed without checking whether it is set with '{check}'",
+            access.field
+        ),
+    ))
+}
+
+/// Computes a fix wrapping the statement containing the optional field access at `range` in a
+/// guard checking whether the field is set.
+pub(crate) fn optional_field_guard_fix(
+    db: &Database,
+    uri: &Arc<Url>,
+    range: Range,
+) -> Option<TextEdit> {
+    let tree = db.parse(Arc::clone(uri))?;
+    let source = db.source(Arc::clone(uri))?;
+
+    let mut node = tree.root_node().named_descendant_for_point_range(range)?;
+    while node.kind() != "field_access" || node.range() != range {
+        node = node.parent()?;
+    }
+
+    let access = optional_access(db, uri, source.as_bytes(), node)?;
+
+    let mut stmt = node;
+    while stmt.kind() != "stmt" {
+        stmt = stmt.parent()?;
+    }
+
+    let range = stmt.range();
+    let indent: String = source
+        .lines()
+        .nth(usize::try_from(range.start.line).ok()?)?
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+
+    // Indent the guarded statement one level deeper, matching the indentation style in use.
+    let unit = if indent.starts_with(' ') {
+        "    "
+    } else {
+        "\t"
+    };
+    let text = stmt
+        .utf8_text(source.as_bytes())
+        .ok()?
+        .replace('\n', &format!("\n{unit}"));
+
+    Some(TextEdit::new(
+        range,
+        format!("if ( {} )\n{indent}{unit}{text}", access.check()),
+    ))
+}
+
+/// Whether the node is assigned to, e.g., `c$http = ...`.
+pub(crate) fn is_write(node: Node) -> bool {
+    let Some(expr) = node.parent().filter(|p| p.kind() == "expr") else {
+        return false;
+    };
+
+    expr.parent().is_some_and(|p| {
+        matches!(
+            p.children().as_slice(),
+            [lhs, op, _] if op.kind() == "=" && lhs.range() == expr.range()
+        )
+    })
+}
+
+/// Whether a node is only reached if the optional field it accesses is set.
+///
+/// We look for enclosing `if` statements, `&&`, `||` and ternary expressions, as well as earlier
+/// statements on the same path which either assign the field or return early if it is unset.
+fn is_guarded(node: Node, access: &OptionalAccess, source: &[u8]) -> bool {
+    let mut child = node;
+    while let Some(parent) = child.parent() {
+        let is = |n: &Node| n.range() == child.range();
+
+        match parent.children().as_slice() {
+            [kw, open, cond, close, then, rest @ ..]
+                if kw.kind() == "if" && open.kind() == "(" && close.kind() == ")" =>
+            {
+                if is(then) && implies(*cond, access, source) {
+                    return true;
+                }
+
+                if let [_, els] = rest {
+                    if is(els) && implies_not(*cond, access, source) {
+                        return true;
+                    }
+                }
+            }
+            [lhs, op, rhs] if op.kind() == "&&" && is(rhs) && implies(*lhs, access, source) => {
+                return true;
+            }
+            [lhs, op, rhs] if op.kind() == "||" && is(rhs) && implies_not(*lhs, access, source) => {
+                return true;
+            }
+            [cond, q, a, colon, b]
+                if q.kind() == "?"
+                    && colon.kind() == ":"
+                    && ((is(a) && implies(*cond, access, source))
+                        || (is(b) && implies_not(*cond, access, source))) =>
+            {
+                return true;
+            }
+            _ => {}
+        }
+
+        // Check statements preceding the current one in the same block.
+        if child.kind() == "stmt" {
+            let guarded = parent
+                .named_children("stmt")
+                .iter()
+                .take_while(|s| s.range().start < child.range().start)
+                .any(|s| {
+                    is_field_assignment(*s, access, source) || is_early_exit(*s, access, source)
+                });
+
+            if guarded {
+                return true;
+            }
+        }
+
+        child = parent;
+    }
+
+    false
+}
+
+/// Whether a statement is an assignment to the field, e.g., `c$http = ...;`.
+fn is_field_assignment(stmt: Node, access: &OptionalAccess, source: &[u8]) -> bool {
+    stmt.named_child("expr")
+        .filter(|e| typecheck::is_assignment(*e))
+        .and_then(|e| e.named_child("expr"))
+        .and_then(|lhs| lhs.named_child("field_access"))
+        .is_some_and(|lhs| access.is_assigned_by(lhs, source))
+}
+
+/// Whether a statement is a guard like `if ( ! c?$http ) return;`.
+fn is_early_exit(stmt: Node, access: &OptionalAccess, source: &[u8]) -> bool {
+    match stmt.children().as_slice() {
+        [kw, open, cond, close, then]
+            if kw.kind() == "if" && open.kind() == "(" && close.kind() == ")" =>
+        {
+            implies_not(*cond, access, source) && is_jump(*then)
+        }
+        _ => false,
+    }
+}
+
+/// Whether a statement unconditionally leaves the current block, i.e., is a `return`, `next` or
+/// `break`, or a block ending in one.
+fn is_jump(stmt: Node) -> bool {
+    match stmt.children().as_slice() {
+        [kw, ..] if !kw.is_named() && matches!(kw.kind(), "return" | "next" | "break") => true,
+        [open, .., close] if open.kind() == "{" && close.kind() == "}" => stmt
+            .named_child("stmt_list")
+            .unwrap_or(stmt)
+            .named_children("stmt")
+            .last()
+            .is_some_and(|s| is_jump(*s)),
+        _ => false,
+    }
+}
+
+/// Whether the condition implies that the field is set.
+fn implies(cond: Node, access: &OptionalAccess, source: &[u8]) -> bool {
+    match cond.children().as_slice() {
+        [open, expr, close] if open.kind() == "(" && close.kind() == ")" => {
+            implies(*expr, access, source)
+        }
+        [lhs, op, rhs] if op.kind() == "&&" => {
+            implies(*lhs, access, source) || implies(*rhs, access, source)
+        }
+        [expr] => implies(*expr, access, source),
+        _ => access.is_checked_by(cond, source),
+    }
+}
+
+/// Whether the condition being false implies that the field is set.
+fn implies_not(cond: Node, access: &OptionalAccess, source: &[u8]) -> bool {
+    match cond.children().as_slice() {
+        [open, expr, close] if open.kind() == "(" && close.kind() == ")" => {
+            implies_not(*expr, access, source)
+        }
+        [lhs, op, rhs] if op.kind() == "||" => {
+            implies_not(*lhs, access, source) || implies_not(*rhs, access, source)
+        }
+        [not, expr] if not.kind() == "!" => implies(*expr, access, source),
+        [expr] => implies_not(*expr, access, source),
+        _ => false,
+    }
+}
+
+/// Whether two expressions are syntactically the same, ignoring whitespace and comments.
+fn same_expr(a: Node, b: Node, source: &[u8]) -> bool {
+    let (a_children, b_children) = (a.children(), b.children());
+
+    if a.kind() != b.kind() || a_children.len() != b_children.len() {
+        return false;
+    }
+
+    if a_children.is_empty() {
+        return a.utf8_text(source).ok() == b.utf8_text(source).ok();
+    }
+
+    a_children
+        .iter()
+        .zip(&b_children)
+        .all(|(a, b)| same_expr(*a, *b, source))
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::sync::Arc;
+
+    use tower_lsp::lsp_types::{DiagnosticSeverity, DiagnosticTag, NumberOrString, Position, Url};
+
+    use crate::{
+        ast::Ast,
+        lint::{Level, Rule},
+        lsp::{InitializationOptions, TestDatabase},
+        parse::Parse,
+        Client,
+    };
+
+    const PRELUDE: &str = "type Info: record { uri: string; };
+type connection: record { http: Info &optional; id: count; };
+";
+
+    fn diagnostics(source: &str) -> Vec<u32> {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), format!("{PRELUDE}{source}"));
+
+        super::diagnostics(&db.0, uri)
+            .into_iter()
+            .map(|d| d.range.start.line - 2)
+            .collect()
+    }
+
+    #[test]
+    fn rules() {
+        for rule in super::Rule::ALL {
+            assert_eq!(super::Rule::from_code(rule.code()), Some(rule));
+            assert!(rule.code_description().is_some());
+        }
+    }
+
+    #[test]
+    fn suppressions() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        let source = r##"# zeek-lsp: ignore[unused-local]
+local x = 1;
+local y = 2; # zeek-lsp: ignore
+local z = 3; #zeek-lsp:ignore[shadowed-declaration, unused-local]
+local w = 4;
+print "# zeek-lsp: ignore";"##;
+        db.add_file((*uri).clone(), source);
+
+        let tree = db.0.parse(uri).unwrap();
+        let suppressions = super::Suppressions::parse(tree.root_node(), source);
+
+        assert!(suppressions.is_suppressed(Rule::UnusedLocal, 1));
+        assert!(!suppressions.is_suppressed(Rule::ShadowedDeclaration, 1));
+        assert!(suppressions.is_suppressed(Rule::ShadowedDeclaration, 2));
+        assert!(suppressions.is_suppressed(Rule::ShadowedDeclaration, 3));
+        assert!(suppressions.is_suppressed(Rule::UnusedLocal, 3));
+        assert!(!suppressions.is_suppressed(Rule::OptionalFieldAccess, 3));
+        assert!(!suppressions.is_suppressed(Rule::UnusedLocal, 4));
+
+        // Text in strings which looks like a suppression does not suppress anything.
+        assert!(!suppressions.is_suppressed(Rule::UnusedLocal, 5));
+    }
+
+    #[test]
+    fn levels() {
+        let source = "function f(x: count) {
+    local a = 1; # zeek-lsp: ignore[unused-local]
+    local b = 2;
+}";
+        let mut db = TestDatabase::default();
+        let mut options = InitializationOptions::new();
+        options
+            .lints
+            .insert("unused-parameter".into(), Level::Error);
+        options.lints.insert("unused-local".into(), Level::Hint);
+        db.0.set_initialization_options(Arc::new(options));
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), source);
+
+        let diags = super::diagnostics(&db.0, uri);
+        assert_eq!(
+            diags
+                .iter()
+                .map(|d| (
+                    d.range.start.line,
+                    d.severity.unwrap(),
+                    d.code.clone().unwrap()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    0,
+                    DiagnosticSeverity::ERROR,
+                    NumberOrString::String("unused-parameter".into())
+                ),
+                (
+                    2,
+                    DiagnosticSeverity::HINT,
+                    NumberOrString::String("unused-local".into())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn optional_field_access() {
+        assert_eq!(
+            diagnostics(
+                r#"event e(c: connection) {
+    print c$http$uri;
+    print c$id;
+    if ( c?$http )
+        print c$http$uri;
+    if ( c?$http && c$http$uri == "/" )
+        print c$http;
+    if ( ! c?$http )
+        print "unset";
+    else
+        print c$http;
+    print c?$http ? c$http$uri : "";
+    c$http = Info($uri="/");
+}
+event f(c: connection) {
+    if ( ! c?$http )
+        return;
+    print c$http$uri;
+}
+event g(c: connection) {
+    c$http = Info($uri="/");
+    print c$http$uri;
+}
+event h(c: connection) {
+    if ( ! c?$http )
+        print "return";
+    print c$http$uri;
+    if ( ! c?$http )
+        { if ( c$id == 0 ) return; }
+    print c$http$uri;
+    if ( ( c ?$ http ) && c$id == 0 )
+        print c$http$uri;
+    if ( c$id == 0 || ! ( c?$http ) )
+        { print "unset"; return; }
+    print c$http$uri;
+}
+"#
+            ),
+            vec![1, 26, 29]
+        );
+    }
+
+    #[test]
+    fn unused() {
+        let source = r"function f(x: count, y: count, _z: count): count {
+    local a = 1;
+    local b = 2;
+    local c: count;
+    c = 3;
+    local _d = 4;
+    return x + b;
+}
+event e(c: connection) { }
+";
+        assert_eq!(diagnostics(source), vec![0, 1, 3]);
+
+        let mut db = TestDatabase::default();
+        let mut options = InitializationOptions::new();
+        options.unused_handler_parameters = true;
+        db.0.set_initialization_options(Arc::new(options));
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), format!("{PRELUDE}{source}"));
+
+        let diags = super::diagnostics(&db.0, uri);
+        assert_eq!(
+            diags.iter().map(|d| d.message.as_str()).collect::<Vec<_>>(),
+            [
+                "parameter 'y' is never used",
+                "local variable 'a' is never used",
+                "local variable 'c' is never used",
+                "parameter 'c' is never used",
+            ]
+        );
+        assert!(diags
+            .iter()
+            .all(|d| d.tags == Some(vec![DiagnosticTag::UNNECESSARY])));
+    }
+
+    #[test]
+    fn shadowing() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            "global g = 1;
+function f(x: count) {
+    local x = 2;
+    local g = 3;
+    local y = 4;
+    print x, g, y;
+}",
+        );
+
+        let diags = super::diagnostics(&db.0, uri);
+        assert_eq!(
+            diags
+                .iter()
+                .map(|d| (d.range.start.line, d.message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (1, "parameter 'x' is never used"),
+                (2, "local 'x' shadows parameter 'x'"),
+                (3, "local 'g' shadows global 'g'"),
+            ]
+        );
+
+        let related = diags[2].related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.range.start.line, 0);
+    }
+
+    #[test]
+    fn redeclarations() {
+        let mut db = TestDatabase::default();
+        db.add_file(
+            Url::from_file_path("/y.zeek").unwrap(),
+            "global g: count;
+global h: count;
+function f() { }
+event e() { }",
+        );
+
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            "@load ./y
+global g: string;
+global h:  count;
+function f() { }
+event e() { }",
+        );
+
+        let diags = super::diagnostics(&db.0, uri);
+        assert_eq!(
+            diags
+                .iter()
+                .map(|d| (d.range.start.line, d.severity.unwrap(), d.message.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    1,
+                    DiagnosticSeverity::WARNING,
+                    "global 'g' is declared with type 'string' here but with type 'count' elsewhere"
+                ),
+                (
+                    3,
+                    DiagnosticSeverity::ERROR,
+                    "function 'f' is defined more than once"
+                ),
+            ]
+        );
+
+        assert!(diags.iter().all(|d| {
+            d.related_information.as_ref().unwrap()[0].location.uri
+                == Url::from_file_path("/y.zeek").unwrap()
+        }));
+    }
+
+    #[test]
+    fn optional_field_guard_fix() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            format!("{PRELUDE}event e(c: connection) {{\n    print c$http$uri;\n}}"),
+        );
+
+        let diag = super::diagnostics(&db.0, Arc::clone(&uri))
+            .into_iter()
+            .next()
+            .unwrap();
+
+        let fix = super::optional_field_guard_fix(&db.0, &uri, diag.range).unwrap();
+        assert_eq!(fix.new_text, "if ( c?$http )\n        print c$http$uri;");
+    }
+
+    #[test]
+    fn bare_mode() {
+        let mut db = TestDatabase::default();
+        db.add_prefix("/p");
+        db.add_file(
+            Url::from_file_path("/p/base/init-bare.zeek").unwrap(),
+            "global a: count;",
+        );
+        db.add_file(
+            Url::from_file_path("/p/base/init-default.zeek").unwrap(),
+            "@load base/frameworks/b\n",
+        );
+        db.add_file(
+            Url::from_file_path("/p/base/frameworks/b.zeek").unwrap(),
+            "global b: count;",
+        );
+
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), "event zeek_init() { print a, b; }");
+
+        assert!(super::check_bare_mode(&db.0, &uri).is_empty());
+
+        db.0.set_bare_mode(true);
+        assert_eq!(
+            super::check_bare_mode(&db.0, &uri)
+                .into_iter()
+                .map(|d| (d.range.start, d.message))
+                .collect::<Vec<_>>(),
+            vec![(
+                Position::new(0, 29),
+                "'b' is only available when Zeek loads its default scripts, add '@load base/frameworks/b'"
+                    .to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,363 @@
+//! Extraction of log schemas from `Log::create_stream` calls.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, LazyLock},
+};
+
+use eyre::Result;
+use itertools::Itertools;
+use rustc_hash::FxHashSet;
+use serde::Serialize;
+use tower_lsp::lsp_types::{Range, Url};
+
+use crate::{
+    ast::Ast,
+    lsp::Database,
+    parse::Parse,
+    query::{Decl, DeclKind, NodeLocation, Query},
+    Files, Str,
+};
+
+/// Maximal depth of nested records which are flattened into columns.
+const MAX_DEPTH: usize = 8;
+
+/// Output format of log schemas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Markdown,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        <Self as clap::ValueEnum>::from_str(s, false)
+            .map_err(|_| format!("unknown format '{s}', expected 'json' or 'markdown'"))
+    }
+}
+
+/// A column of a log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Column {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub typ: String,
+
+    /// File the field backing this column was declared in.
+    pub file: Option<Url>,
+}
+
+/// A log stream created with `Log::create_stream`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Stream {
+    /// ID of the stream, e.g., `Conn::LOG`.
+    pub id: String,
+
+    /// Default path of the log, e.g., `conn`.
+    pub path: Option<String>,
+
+    /// Record type holding the columns, e.g., `Conn::Info`.
+    pub columns_type: String,
+
+    /// File the stream is created in.
+    pub file: Url,
+
+    pub columns: Vec<Column>,
+}
+
+/// Get the files visible from the given files, i.e., the files themselves, all files they load
+/// and all files Zeek loads implicitly.
+#[must_use]
+pub fn loaded_script_set(db: &Database, files: &[Arc<Url>]) -> Vec<Arc<Url>> {
+    files
+        .iter()
+        .flat_map(|f| {
+            std::iter::once(Arc::clone(f)).chain(db.loaded_files_recursive(Arc::clone(f)).to_vec())
+        })
+        .chain(db.implicit_loads().iter().flat_map(|f| {
+            std::iter::once(Arc::clone(f)).chain(db.loaded_files_recursive(Arc::clone(f)).to_vec())
+        }))
+        .unique()
+        .collect()
+}
+
+/// Get all log streams created in the given set of files.
+#[must_use]
+pub fn streams(db: &Database, files: &[Arc<Url>]) -> Vec<Stream> {
+    static COLUMNS: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new(r"\$columns\s*=\s*([\w:]+)").expect("valid regex"));
+    static PATH: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new(r#"\$path\s*=\s*"([^"]*)""#).expect("valid regex"));
+
+    files
+        .iter()
+        .flat_map(|uri| {
+            db.function_calls(Arc::clone(uri))
+                .iter()
+                .filter_map(|call| {
+                    if text(db, &call.f.uri, call.f.range)? != "Log::create_stream" {
+                        return None;
+                    }
+
+                    let id = text(db, uri, call.args.first()?.range)?;
+                    let args = call.args.get(1)?;
+                    let args_text = text(db, uri, args.range)?;
+
+                    let columns_type = Str::from(&COLUMNS.captures(&args_text)?[1]);
+                    let path = PATH.captures(&args_text).map(|cap| cap[1].to_string());
+
+                    let decl = db.resolve_id(
+                        columns_type,
+                        NodeLocation::from_range(Arc::clone(uri), args.range),
+                    )?;
+
+                    Some(Stream {
+                        id,
+                        path,
+                        columns_type: decl.fqid.to_string(),
+                        file: (**uri).clone(),
+                        columns: columns(db, &decl, files),
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .sorted_by(|a, b| a.id.cmp(&b.id))
+        .collect()
+}
+
+/// Get the logged columns of a record type, including fields from all redefs in `files`.
+fn columns(db: &Database, decl: &Decl, files: &[Arc<Url>]) -> Vec<Column> {
+    let mut columns = Vec::new();
+    add_columns(db, decl, files, "", &mut columns, 0);
+    columns
+}
+
+fn add_columns(
+    db: &Database,
+    decl: &Decl,
+    files: &[Arc<Url>],
+    prefix: &str,
+    columns: &mut Vec<Column>,
+    depth: usize,
+) {
+    let DeclKind::Type(fields) = &decl.kind else {
+        return;
+    };
+
+    if depth > MAX_DEPTH {
+        return;
+    }
+
+    let is_logged = |d: &Decl| d.attrs.iter().any(|a| a.name == "log");
+
+    // Fields from the declaration and redefs already visible at the point of resolution.
+    let mut all: Vec<(Decl, bool)> = fields
+        .iter()
+        .map(|f| (f.clone(), is_logged(decl) || is_logged(f)))
+        .collect();
+
+    // Redefs anywhere in the script set contribute to the final columns.
+    let mut seen: FxHashSet<_> = all.iter().map(|(f, _)| f.clone()).collect();
+    for f in files {
+        for redef in db.decls(Arc::clone(f)).iter() {
+            let DeclKind::RedefRecord(fields) = &redef.kind else {
+                continue;
+            };
+            if redef.fqid != decl.fqid {
+                continue;
+            }
+
+            for field in fields {
+                if seen.insert(field.clone()) {
+                    all.push((field.clone(), is_logged(redef) || is_logged(field)));
+                }
+            }
+        }
+    }
+
+    for (field, logged) in all {
+        if !logged {
+            continue;
+        }
+
+        let name = format!("{prefix}{}", field.id);
+        let typ = field_type(db, &field).unwrap_or_default();
+
+        // Records are flattened into their logged fields.
+        let nested = field.loc.as_ref().and_then(|loc| {
+            db.resolve_id(
+                Str::from(typ.as_str()),
+                NodeLocation::from_range(Arc::clone(&loc.uri), loc.range),
+            )
+        });
+
+        if let Some(nested) = nested.filter(|d| matches!(d.kind, DeclKind::Type(_))) {
+            add_columns(db, &nested, files, &format!("{name}."), columns, depth + 1);
+        } else {
+            columns.push(Column {
+                name,
+                typ,
+                file: field.loc.as_ref().map(|loc| (*loc.uri).clone()),
+            });
+        }
+    }
+}
+
+/// Get the source text of the node at the given range.
+fn text(db: &Database, uri: &Arc<Url>, range: Range) -> Option<String> {
+    let source = db.source(Arc::clone(uri))?;
+    let tree = db.parse(Arc::clone(uri))?;
+    let node = tree.root_node().named_descendant_for_point_range(range)?;
+    Some(node.utf8_text(source.as_bytes()).ok()?.to_string())
+}
+
+/// Get the declared type of a record field, e.g., `count` for `c: count &log;`.
+fn field_type(db: &Database, field: &Decl) -> Option<String> {
+    let loc = field.loc.as_ref()?;
+    let text = text(db, &loc.uri, loc.range)?;
+    let (_, typ) = text.split_once(':')?;
+    let typ = typ.split('&').next()?;
+    Some(typ.trim().trim_end_matches(';').trim().to_string())
+}
+
+/// Render log streams in the given format.
+///
+/// # Errors
+///
+/// Will return `Err` if the streams cannot be serialized.
+pub fn render(streams: &[Stream], format: Format) -> Result<String> {
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(streams)?,
+        Format::Markdown => streams
+            .iter()
+            .map(|s| {
+                let title = match &s.path {
+                    Some(path) => format!("## `{}` (`{path}.log`)", s.id),
+                    None => format!("## `{}`", s.id),
+                };
+
+                let rows = s.columns.iter().map(|c| {
+                    let file = c
+                        .file
+                        .as_ref()
+                        .map_or(String::new(), |f| format!("`{}`", f.path()));
+                    format!("| `{}` | `{}` | {file} |", c.name, c.typ)
+                });
+
+                [
+                    title,
+                    String::new(),
+                    format!(
+                        "Columns of `{}`, stream created in `{}`.",
+                        s.columns_type,
+                        s.file.path()
+                    ),
+                    String::new(),
+                    "| Column | Type | File |".into(),
+                    "| --- | --- | --- |".into(),
+                ]
+                .into_iter()
+                .chain(rows)
+                .join("\n")
+            })
+            .join("\n\n"),
+    })
+}
+
+/// Extract log schemas from scripts outside of a language server session.
+///
+/// # Arguments
+///
+/// * `paths` - scripts, or directories containing scripts, to extract logs from
+/// * `format` - output format
+///
+/// # Errors
+///
+/// Will return `Err` if files cannot be read or the result cannot be rendered.
+pub async fn extract(paths: &[PathBuf], format: Format) -> Result<String> {
+    let (db, roots) = Database::from_scripts(paths).await;
+
+    let files = loaded_script_set(&db, &roots);
+    render(&streams(&db, &files), format)
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::sync::Arc;
+
+    use tower_lsp::lsp_types::Url;
+
+    use crate::{
+        logs::{loaded_script_set, render, streams, Format},
+        lsp::TestDatabase,
+    };
+
+    #[test]
+    fn schema() {
+        let mut db = TestDatabase::default();
+
+        let main = Arc::new(Url::from_file_path("/x/main.zeek").unwrap());
+        db.add_file(
+            (*main).clone(),
+            "module Foo;
+export {
+    redef enum Log::ID += { LOG };
+    type Id: record {
+        orig_h: addr &log;
+        resp_h: addr &log;
+    };
+    type Info: record {
+        ts: time &log;
+        id: Id &log;
+        internal: count;
+    };
+}
+event zeek_init() {
+    Log::create_stream(Foo::LOG, [$columns=Info, $path=\"foo\"]);
+}",
+        );
+
+        let site = Arc::new(Url::from_file_path("/x/site.zeek").unwrap());
+        db.add_file(
+            (*site).clone(),
+            "@load ./main
+redef record Foo::Info += { extra: string &log &optional; };",
+        );
+
+        let db = db.0;
+        let files = loaded_script_set(&db, &[Arc::clone(&site)]);
+        let streams = streams(&db, &files);
+
+        assert_eq!(streams.len(), 1);
+        let stream = &streams[0];
+        assert_eq!(stream.id, "Foo::LOG");
+        assert_eq!(stream.path.as_deref(), Some("foo"));
+        assert_eq!(stream.columns_type, "Foo::Info");
+        assert_eq!(
+            stream
+                .columns
+                .iter()
+                .map(|c| (c.name.as_str(), c.typ.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                ("ts", "time"),
+                ("id.orig_h", "addr"),
+                ("id.resp_h", "addr"),
+                ("extra", "string")
+            ]
+        );
+        assert_eq!(stream.columns[3].file.as_ref(), Some(site.as_ref()));
+
+        let markdown = render(&streams, Format::Markdown).unwrap();
+        assert!(markdown.contains("| `id.orig_h` | `addr` |"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&render(&streams, Format::Json).unwrap()).unwrap();
+        assert_eq!(json[0]["columns"][0]["type"], "time");
+    }
+}
@@ -1,18 +1,28 @@
 pub(crate) use crate::{
-    ast::{load_to_file, Ast},
+    ast::{file_to_load, load_to_file, Ast},
+    btest,
     complete::complete,
+    fix, lint, logs,
     parse::Parse,
+    preproc,
     query::{self, Decl, DeclKind, ModuleId, NodeLocation, Query},
-    zeek, Client, Files, Str,
+    refactor, sig, syntax, typecheck, zeek, zkg, Client, Files, Str,
 };
 use itertools::Itertools;
 use notify::Watcher;
+use path_clean::PathClean;
 use rayon::prelude::*;
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
 use salsa::ParallelDatabase;
 use semver::Version;
-use serde::Deserialize;
-use std::{fmt::Debug, path::PathBuf, sync::Arc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tower_lsp::{
     jsonrpc::{Error, Result},
     lsp_types::{
@@ -22,23 +32,26 @@ use tower_lsp::{
             WorkDoneProgressCreate,
         },
         CodeAction, CodeActionKind, CodeActionParams, CodeActionProviderCapability,
-        CodeActionResponse, CompletionOptions, CompletionParams, CompletionResponse,
-        DeclarationCapability, Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams,
-        DidChangeWatchedFilesParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
-        DidSaveTextDocumentParams, DocumentFormattingParams, DocumentRangeFormattingParams,
-        DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, FileChangeType, FileEvent,
-        FoldingRange, FoldingRangeParams, FoldingRangeProviderCapability, GotoDefinitionParams,
-        GotoDefinitionResponse, Hover, HoverContents, HoverParams, HoverProviderCapability,
-        ImplementationProviderCapability, InitializeParams, InitializeResult, InitializedParams,
-        InlayHint, InlayHintKind, InlayHintLabel, InlayHintParams, InlayHintTooltip, Location,
-        MarkedString, MarkupContent, MarkupKind, MessageType, NumberOrString, OneOf,
-        ParameterInformation, ParameterLabel, Position, ProgressParams, ProgressParamsValue,
-        ProgressToken, Range, ReferenceParams, RenameParams, SemanticTokensFullOptions,
-        SemanticTokensOptions, SemanticTokensParams, SemanticTokensResult,
-        SemanticTokensServerCapabilities, ServerCapabilities, ServerInfo, SignatureHelp,
-        SignatureHelpOptions, SignatureHelpParams, SignatureInformation, SymbolInformation,
-        SymbolKind, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
-        WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+        CodeActionResponse, CodeLens, CodeLensOptions, CodeLensParams, Command, CompletionOptions,
+        CompletionParams, CompletionResponse, DeclarationCapability, Diagnostic,
+        DiagnosticSeverity, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+        DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+        DocumentFormattingParams, DocumentRangeFormattingParams, DocumentSymbol,
+        DocumentSymbolParams, DocumentSymbolResponse, ExecuteCommandOptions, ExecuteCommandParams,
+        FileChangeType, FileEvent, FoldingRange, FoldingRangeParams,
+        FoldingRangeProviderCapability, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+        HoverContents, HoverParams, HoverProviderCapability, ImplementationProviderCapability,
+        InitializeParams, InitializeResult, InitializedParams, InlayHint, InlayHintKind,
+        InlayHintLabel, InlayHintParams, InlayHintTooltip, Location, MarkedString, MarkupContent,
+        MarkupKind, MessageType, OneOf, ParameterInformation, ParameterLabel, Position,
+        ProgressParams, ProgressParamsValue, ProgressToken, Range, ReferenceParams, Registration,
+        RenameParams, SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensParams,
+        SemanticTokensResult, SemanticTokensServerCapabilities, ServerCapabilities, ServerInfo,
+        SignatureHelp, SignatureHelpOptions, SignatureHelpParams, SignatureInformation,
+        SymbolInformation, SymbolKind, SymbolTag, TextDocumentSyncCapability, TextDocumentSyncKind,
+        TextEdit, TypeHierarchyItem, TypeHierarchyPrepareParams, TypeHierarchyRegistrationOptions,
+        TypeHierarchySubtypesParams, TypeHierarchySupertypesParams, Url, WorkDoneProgress,
+        WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
         WorkDoneProgressReport, WorkspaceEdit, WorkspaceSymbolParams,
     },
     LanguageServer, LspService, Server,
@@ -76,7 +89,24 @@ impl Database {
         for u in updates {
             match u {
                 SourceUpdate::Update(uri, source) => {
-                    self.set_unsafe_source(Arc::clone(uri), source.clone());
+                    // Files embedded into btest tests are analyzed as separate documents.
+                    let embedded = btest_embedded_files(uri, source);
+                    for stale in self.btest_embedded_uris(uri) {
+                        if !embedded.iter().any(|(f, _)| *f == stale) {
+                            needs_files_update |= files.remove(&stale);
+                        }
+                    }
+                    for (f, source) in embedded {
+                        self.set_unsafe_source(Arc::clone(&f), source);
+                        needs_files_update |= files.insert(f);
+                    }
+
+                    let source = if btest::is_test(source) {
+                        Str::from(btest::host_source(source))
+                    } else {
+                        source.clone()
+                    };
+                    self.set_unsafe_source(Arc::clone(uri), source);
 
                     if !files.contains(uri) {
                         files.insert(Arc::clone(uri));
@@ -84,6 +114,10 @@ impl Database {
                     }
                 }
                 SourceUpdate::Remove(uri) => {
+                    for f in self.btest_embedded_uris(uri) {
+                        needs_files_update |= files.remove(&f);
+                    }
+
                     if files.contains(uri) {
                         files.remove(uri);
                         needs_files_update = true;
@@ -97,12 +131,93 @@ impl Database {
         }
     }
 
+    /// Creates a database containing the given scripts and the scripts shipped with Zeek for use
+    /// outside of a language server session.
+    ///
+    /// Returns the database and the URIs of the given scripts.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - scripts, or directories containing scripts
+    pub async fn from_scripts(paths: &[PathBuf]) -> (Self, Vec<Arc<Url>>) {
+        let mut db = Self::default();
+
+        let prefixes = zeek::prefixes(None).await.unwrap_or_default();
+        db.set_prefixes(Arc::from(prefixes));
+        db.set_zeek_version(zeek::version().await.ok());
+
+        let scripts = |dir: &Path| {
+            WalkDir::new(dir)
+                .into_iter()
+                .filter_map(std::result::Result::ok)
+                .filter(|e| e.path().extension() == Some("zeek".as_ref()))
+                .map(walkdir::DirEntry::into_path)
+                .collect::<Vec<_>>()
+        };
+
+        let roots: Vec<_> = paths
+            .iter()
+            .flat_map(|p| scripts(p))
+            .filter_map(|p| Url::from_file_path(std::fs::canonicalize(p).ok()?).ok())
+            .map(Arc::new)
+            .collect();
+
+        let system_files = zeek::system_files()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|f| Url::from_file_path(f.path).ok())
+            .map(Arc::new);
+
+        let updates: Vec<_> = roots
+            .iter()
+            .cloned()
+            .chain(system_files)
+            .unique()
+            .filter_map(|uri| {
+                let source = std::fs::read_to_string(uri.to_file_path().ok()?).ok()?;
+                Some(SourceUpdate::Update(uri, source.into()))
+            })
+            .collect();
+        db.update_sources(&updates);
+
+        (db, roots)
+    }
+
+    /// Get the URIs of the files embedded into a btest test known to the database.
+    fn btest_embedded_uris(&self, uri: &Arc<Url>) -> Vec<Arc<Url>> {
+        let Some(source) = self.source(Arc::clone(uri)) else {
+            return Vec::new();
+        };
+
+        btest_embedded_files(uri, &source)
+            .into_iter()
+            .map(|(f, _)| f)
+            .collect()
+    }
+
     fn file_changed(&self, uri: Arc<Url>) {
         // Precompute decls in this file.
         let _d = self.decls(uri);
     }
 }
 
+/// Get the URIs and sources of the Zeek files embedded into a btest test.
+fn btest_embedded_files(uri: &Url, source: &str) -> Vec<(Arc<Url>, Str)> {
+    if !btest::is_test(source) {
+        return Vec::new();
+    }
+
+    btest::embedded_files(source)
+        .into_iter()
+        .filter(|f| Path::new(f.name.as_str()).extension() == Some("zeek".as_ref()))
+        .filter_map(|f| {
+            let uri = btest::embedded_uri(uri, &f.name)?;
+            Some((Arc::new(uri), Str::from(f.source)))
+        })
+        .collect()
+}
+
 impl Default for Database {
     fn default() -> Self {
         let mut db = Self {
@@ -112,6 +227,10 @@ impl Default for Database {
         db.set_files(Arc::default());
         db.set_prefixes(Arc::default());
         db.set_workspace_folders(Arc::default());
+        db.set_zeek_version(None);
+        db.set_defines(Arc::default());
+        db.set_bare_mode(false);
+        db.set_packages(Arc::default());
         db.set_capabilities(Arc::default());
         db.set_initialization_options(Arc::new(InitializationOptions::new()));
 
@@ -140,6 +259,9 @@ pub struct Backend {
     pub client: Option<tower_lsp::Client>,
     state: tokio::sync::RwLock<Database>,
     file_watcher: Option<tokio::sync::Mutex<notify::RecommendedWatcher>>,
+
+    /// Diagnostics from the last btest run of each test.
+    btest_results: tokio::sync::Mutex<FxHashMap<Url, Vec<Diagnostic>>>,
 }
 
 enum ParseResult {
@@ -148,6 +270,36 @@ enum ParseResult {
 }
 
 impl Backend {
+    /// Handler for the custom `zeek/status` request reporting the state of the server.
+    pub(crate) async fn status(&self) -> Result<Value> {
+        let state = self.state.read().await;
+
+        Ok(json!({
+            "zeek_version": state.zeek_version().map(|v| v.to_string()),
+            "prefixes": state.prefixes().as_ref(),
+            "files": state.files().len(),
+        }))
+    }
+
+    /// Handler for the custom `zeek/enumMembers` request listing all members of an enum across
+    /// all known files.
+    pub(crate) async fn enum_members(&self, params: EnumMembersParams) -> Result<Vec<EnumMember>> {
+        let state = self.state.read().await;
+
+        Ok(state
+            .enum_members(Str::from(params.id))
+            .iter()
+            .map(|m| EnumMember {
+                id: m.fqid.to_string(),
+                location: m
+                    .loc
+                    .as_ref()
+                    .map(|loc| Location::new((*loc.uri).clone(), loc.range)),
+                documentation: m.documentation.to_string(),
+            })
+            .collect())
+    }
+
     async fn client_message<M>(&self, level: MessageType, message: M)
     where
         M: std::fmt::Display,
@@ -254,11 +406,16 @@ impl Backend {
     async fn file_changed(&self, uri: Arc<Url>) -> Result<ParseResult> {
         if let Some(client) = &self.client {
             let state = self.state.read().await;
-            let diags = {
+            let mut diags = {
                 state.file_changed(Arc::clone(&uri));
 
-                if let Some(tree) = state.parse(Arc::clone(&uri)) {
-                    tree_diagnostics(&tree.root_node())
+                if let (Some(tree), Some(source)) = (
+                    state.parse(Arc::clone(&uri)),
+                    state.source(Arc::clone(&uri)),
+                ) {
+                    syntax::diagnostics(tree.root_node(), &source)
+                } else if let Some(file) = state.parse_sig(Arc::clone(&uri)) {
+                    sig_diagnostics(&file)
                 } else {
                     Vec::new()
                 }
@@ -270,6 +427,13 @@ impl Backend {
                 ParseResult::HasDiagnostics
             };
 
+            diags.extend(file_diagnostics(&state, &uri));
+            diags.extend(btest_embedded_diagnostics(&state, &uri));
+
+            if let Some(results) = self.btest_results.lock().await.get(&uri) {
+                diags.extend(results.iter().cloned());
+            }
+
             client
                 .publish_diagnostics((*uri).clone(), diags, None)
                 .await;
@@ -301,7 +465,7 @@ impl Backend {
                     .filter_map(std::result::Result::ok)
                     .filter(|e| !e.file_type().is_dir())
                     .filter_map(|f| {
-                        if f.path().extension()? == "zeek" {
+                        if matches!(f.path().extension()?.to_str()?, "zeek" | "sig") {
                             Url::from_file_path(f.path()).ok()
                         } else {
                             None
@@ -336,6 +500,41 @@ impl Backend {
         Some(latest)
     }
 
+    /// Run a btest test and publish its results as diagnostics.
+    async fn run_btest(&self, uri: Url, update_baseline: bool) -> Result<Value> {
+        let file = uri
+            .to_file_path()
+            .map_err(|()| Error::invalid_params(format!("'{uri}' is not a file")))?;
+
+        let config = btest::find_config(&file).await.ok_or_else(|| {
+            Error::invalid_params(format!("no '{}' found for '{uri}'", btest::CONFIG_FILE))
+        })?;
+
+        let progress = self.progress_begin("Running btest").await;
+        let outcome = btest::run(&config, &file, update_baseline).await;
+        self.progress_end(progress).await;
+
+        let outcome = outcome.map_err(|e| {
+            error!("could not run btest: {e}");
+            Error::internal_error()
+        })?;
+
+        let Some(source) = self.state.read().await.source(Arc::new(uri.clone())) else {
+            return Err(Error::invalid_params(format!("unknown file '{uri}'")));
+        };
+
+        self.btest_results
+            .lock()
+            .await
+            .insert(uri.clone(), btest::diagnostics(&source, &outcome));
+
+        if let Err(e) = self.file_changed(Arc::new(uri)).await {
+            error!("could not publish test results: {e}");
+        }
+
+        Ok(json!({ "passed": outcome.passed }))
+    }
+
     /// This is wrapper around `zeek::check` directly publishing diagnostics.
     async fn check(&self, uri: Url, version: Option<i32>) {
         // If we have not client to publish to there is no need to run checks.
@@ -343,6 +542,11 @@ impl Backend {
             return;
         };
 
+        // Zeek can only check Zeek scripts.
+        if sig::is_sig_file(&uri) {
+            return;
+        }
+
         let Ok(file) = uri.to_file_path() else {
             return;
         };
@@ -418,12 +622,26 @@ impl LanguageServer for Backend {
             let mut state = self.state.write().await;
             state.set_workspace_folders(Arc::from(workspace_folders));
             state.set_capabilities(Arc::new(params.capabilities));
-            state.set_initialization_options(Arc::new(
-                params
-                    .initialization_options
-                    .and_then(|options| serde_json::from_value(options).ok())
-                    .unwrap_or_else(InitializationOptions::new),
-            ));
+            let initialization_options: InitializationOptions = params
+                .initialization_options
+                .and_then(|options| serde_json::from_value(options).ok())
+                .unwrap_or_else(InitializationOptions::new);
+
+            state.set_zeek_version(
+                initialization_options
+                    .zeek_version
+                    .as_deref()
+                    .and_then(preproc::parse_version),
+            );
+            state.set_defines(
+                initialization_options
+                    .defines
+                    .iter()
+                    .map(|d| Str::from(d.as_str()))
+                    .collect(),
+            );
+            state.set_bare_mode(initialization_options.bare_mode);
+            state.set_initialization_options(Arc::new(initialization_options));
         }
 
         // Check prerequisites and set system prefixes.
@@ -437,6 +655,44 @@ impl LanguageServer for Backend {
             }
         }
 
+        // Detect zkg packages in the workspace and installed packages.
+        {
+            let mut packages = zkg::installed_packages().await.unwrap_or_else(|e| {
+                warn!("cannot detect installed zkg packages: {e}");
+                Vec::new()
+            });
+
+            let workspace_folders: Vec<_> = self
+                .state
+                .read()
+                .await
+                .workspace_folders()
+                .iter()
+                .filter_map(|f| f.to_file_path().ok())
+                .collect();
+            match tokio::task::spawn_blocking(move || {
+                workspace_folders
+                    .iter()
+                    .flat_map(|dir| zkg::packages_in(dir))
+                    .collect::<Vec<_>>()
+            })
+            .await
+            {
+                Ok(workspace_packages) => packages.extend(workspace_packages),
+                Err(e) => warn!("cannot detect zkg packages in workspace: {e}"),
+            }
+
+            self.state.write().await.set_packages(Arc::from(packages));
+        }
+
+        // Detect the Zeek version unless it was pinned by the client.
+        if self.state.read().await.zeek_version().is_none() {
+            match zeek::version().await {
+                Ok(version) => self.state.write().await.set_zeek_version(Some(version)),
+                Err(e) => warn!("cannot detect Zeek version: {e}"),
+            }
+        }
+
         let initialization_options = self.state.read().await.initialization_options();
         let has_zeek_format = zeek::has_format().await;
 
@@ -449,7 +705,7 @@ impl LanguageServer for Backend {
                 document_symbol_provider: Some(OneOf::Left(true)),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
                 completion_provider: Some(CompletionOptions {
-                    trigger_characters: Some(vec!["$".into(), ":".into()]),
+                    trigger_characters: Some(vec!["$".into(), ":".into(), "&".into()]),
                     ..CompletionOptions::default()
                 }),
                 declaration_provider: Some(DeclarationCapability::Simple(true)),
@@ -463,7 +719,19 @@ impl LanguageServer for Backend {
                 document_formatting_provider: Some(OneOf::Left(has_zeek_format)),
                 document_range_formatting_provider: Some(OneOf::Left(has_zeek_format)),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        COMMAND_GENERATE_HANDLER.into(),
+                        COMMAND_BTEST_RUN.into(),
+                        COMMAND_BTEST_UPDATE_BASELINE.into(),
+                        COMMAND_LOG_SCHEMA.into(),
+                    ],
+                    ..ExecuteCommandOptions::default()
+                }),
                 inlay_hint_provider: Some(OneOf::Left(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(true),
+                }),
                 references_provider: Some(OneOf::Left(initialization_options.references)),
                 rename_provider: Some(OneOf::Left(initialization_options.rename)),
                 semantic_tokens_provider: if initialization_options.semantic_highlighting {
@@ -490,6 +758,31 @@ impl LanguageServer for Backend {
     async fn initialized(&self, _: InitializedParams) {
         let initialization_options = self.state.read().await.initialization_options();
 
+        // Type hierarchies can only be offered with dynamic registration since there is no static
+        // server capability for them in the protocol types we use.
+        let type_hierarchy = self
+            .state
+            .read()
+            .await
+            .capabilities()
+            .text_document
+            .as_ref()
+            .and_then(|t| t.type_hierarchy.as_ref())
+            .and_then(|t| t.dynamic_registration)
+            .unwrap_or(false);
+        if type_hierarchy {
+            if let Some(client) = &self.client {
+                let registration = Registration {
+                    id: "zeek/typeHierarchy".into(),
+                    method: "textDocument/prepareTypeHierarchy".into(),
+                    register_options: Some(json!(TypeHierarchyRegistrationOptions::default())),
+                };
+                if let Err(e) = client.register_capability(vec![registration]).await {
+                    warn!("could not register type hierarchy support: {e}");
+                }
+            }
+        }
+
         // Check whether a newer release is available.
         if initialization_options.check_for_updates {
             if let Some(latest) = self.get_latest_release(None).await {
@@ -676,6 +969,9 @@ impl LanguageServer for Backend {
                 changes.text.as_str().into(),
             )]);
 
+        // Results of previous test runs refer to the old source.
+        self.btest_results.lock().await.remove(&uri);
+
         // Diagnostics are already triggered from `file_changed`.
         if let Err(e) = self.file_changed(uri).await {
             error!("could not apply file change: {e}");
@@ -724,6 +1020,22 @@ impl LanguageServer for Backend {
 
         let mut contents = Vec::new();
 
+        // If we are on a `@if` directive show how it evaluates.
+        if let Some(preproc::Condition::If(expr)) = source
+            .lines()
+            .nth(usize::try_from(position.line).unwrap_or(usize::MAX))
+            .and_then(preproc::condition)
+        {
+            let version = state.zeek_version();
+            let message = match (version.as_ref(), preproc::eval_if(expr, version.as_ref())) {
+                (None, _) => "Cannot evaluate condition since the Zeek version is unknown".into(),
+                (Some(v), None) => format!("Cannot evaluate condition with Zeek `{v}`"),
+                (Some(v), Some(true)) => format!("Evaluates to `T` with Zeek `{v}`"),
+                (Some(v), Some(false)) => format!("Evaluates to `F` with Zeek `{v}`"),
+            };
+            contents.push(MarkedString::String(message));
+        }
+
         match node.kind() {
             "id" => {
                 if let Some(decl) = &state.resolve(NodeLocation::from_node(uri, node)) {
@@ -756,7 +1068,42 @@ impl LanguageServer for Backend {
                         contents.push(MarkedString::String(format!("Type: `{}`", typ.fqid)));
                     }
 
+                    if !decl.attrs.is_empty() {
+                        contents.push(MarkedString::String(format!(
+                            "Attributes: {}",
+                            decl.attrs
+                                .iter()
+                                .map(|a| format!("`&{}`", a.name))
+                                .join(" ")
+                        )));
+                    }
+
                     contents.push(MarkedString::String(decl.documentation.to_string()));
+
+                    if matches!(decl.kind, DeclKind::Field) {
+                        let docs = FieldInfo::new(&state, decl).documentation();
+                        if !docs.is_empty() {
+                            contents.push(MarkedString::String(docs));
+                        }
+                    }
+
+                    if let DeclKind::Type(fields) = &decl.kind {
+                        if !fields.is_empty() {
+                            contents.push(MarkedString::String(record_fields(&state, fields)));
+                        }
+                    }
+                }
+            }
+            "attr" => {
+                let name = text
+                    .trim_start_matches('&')
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .next()
+                    .unwrap_or_default();
+
+                if let Some(docs) = query::attr_documentation(name) {
+                    contents.push(MarkedString::String(format!("### attribute `&{name}`")));
+                    contents.push(MarkedString::String(docs.to_string()));
                 }
             }
             "file" => {
@@ -766,10 +1113,15 @@ impl LanguageServer for Backend {
                     uri.as_ref(),
                     state.files().as_ref(),
                     state.prefixes().as_ref(),
+                    state.packages().as_ref(),
                 );
                 if let Some(uri) = uri {
                     contents.push(MarkedString::String(format!("`{}`", uri.path())));
                 }
+
+                if let Some(package) = state.packages().iter().find(|p| p.name == text) {
+                    contents.push(MarkedString::String(package.documentation()));
+                }
             }
             "zeekygen_head_comment" | "zeekygen_prev_comment" | "zeekygen_next_comment" => {
                 // If we are in a zeekygen comment try to recover an identifier under the cursor and use it as target.
@@ -831,6 +1183,10 @@ impl LanguageServer for Backend {
     ) -> Result<Option<DocumentSymbolResponse>> {
         let uri = Arc::new(params.text_document.uri);
 
+        if let Some(file) = self.state.read().await.parse_sig(Arc::clone(&uri)) {
+            return Ok(Some(DocumentSymbolResponse::Nested(sig_symbols(&file))));
+        }
+
         let symbol = |d: &Decl| -> Option<DocumentSymbol> {
             let loc = d.loc.as_ref()?;
 
@@ -842,7 +1198,7 @@ impl LanguageServer for Backend {
                 kind: to_symbol_kind(&d.kind),
                 deprecated: None,
                 detail: None,
-                tags: None,
+                tags: to_symbol_tags(d),
                 children: match &d.kind {
                     DeclKind::Type(fields)
                     | DeclKind::RedefRecord(fields)
@@ -859,7 +1215,7 @@ impl LanguageServer for Backend {
                                     deprecated: None,
                                     children: None,
                                     kind: to_symbol_kind(&f.kind),
-                                    tags: None,
+                                    tags: to_symbol_tags(f),
                                     detail: None,
                                 })
                             })
@@ -937,7 +1293,7 @@ impl LanguageServer for Backend {
                         location: Location::new(loc.uri.as_ref().clone(), loc.range),
                         container_name: Some(format!("{}", d.module)),
 
-                        tags: None,
+                        tags: to_symbol_tags(&d),
                         deprecated: None,
                     })
                 })
@@ -964,6 +1320,12 @@ impl LanguageServer for Backend {
 
         let state = self.state.read().await;
 
+        if let Some(file) = state.parse_sig(Arc::clone(&uri)) {
+            return Ok(
+                sig_definition(&state, &uri, &file, position).map(GotoDefinitionResponse::Scalar)
+            );
+        }
+
         let tree = state.parse(Arc::clone(&uri));
         let Some(tree) = tree.as_ref() else {
             return Ok(None);
@@ -997,6 +1359,7 @@ impl LanguageServer for Backend {
                         uri.as_ref(),
                         state.files().as_ref(),
                         state.prefixes().as_ref(),
+                        state.packages().as_ref(),
                     )
                     .map(|uri| Location::new((*uri).clone(), Range::default()))
                 }
@@ -1312,142 +1675,489 @@ impl LanguageServer for Backend {
             return Ok(None);
         }
 
-        let response = state
-            .files()
+        Ok(Some(implementations(&state, &decl).into()))
+    }
+
+    #[instrument]
+    async fn prepare_type_hierarchy(
+        &self,
+        params: TypeHierarchyPrepareParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let params = params.text_document_position_params;
+        let uri = Arc::new(params.text_document.uri);
+        let position = params.position;
+
+        let state = self.state.read().await;
+
+        // Identifiers in `redef`s resolve to the type they extend, so look for them first.
+        let redef = state
+            .decls(Arc::clone(&uri))
             .iter()
-            .flat_map(|f| {
-                state
-                    .decls(Arc::clone(f))
-                    .iter()
-                    .cloned()
-                    .collect::<Vec<_>>()
-            })
-            .filter(|d| {
-                matches!(
-                    &d.kind,
-                    DeclKind::EventDef(_) | DeclKind::FuncDef(_) | DeclKind::HookDef(_)
-                )
-            })
-            .filter_map(|d| {
-                let loc = &d.loc.as_ref()?;
-                if d.id == decl.id {
-                    Some(Location::new((*loc.uri).clone(), loc.range))
-                } else {
-                    None
-                }
+            .find(|d| {
+                is_type_redef(d)
+                    && d.loc.as_ref().is_some_and(|l| {
+                        l.selection_range.start <= position && position <= l.selection_range.end
+                    })
             })
-            .collect::<Vec<_>>();
+            .cloned();
 
-        Ok(Some(response.into()))
+        let decl = redef.or_else(|| {
+            let tree = state.parse(Arc::clone(&uri))?;
+            let node = tree.root_node().named_descendant_for_position(position)?;
+            let decl = state.resolve(NodeLocation::from_node(Arc::clone(&uri), node))?;
+
+            // Prefer the declaration of the type over any merged view of it.
+            type_decls(&state, &decl.fqid)
+                .into_iter()
+                .find(|d| !is_type_redef(d))
+        });
+
+        Ok(decl
+            .as_ref()
+            .and_then(type_hierarchy_item)
+            .map(|item| vec![item]))
     }
 
     #[instrument]
-    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
-        // For now we only work on the first diagnostic on something missing in the source.
-        let Some(diag) = params
-            .context
-            .diagnostics
-            .iter()
-            .find(|d| d.code == Some(NumberOrString::Number(ERROR_CODE_IS_MISSING)))
+    async fn supertypes(
+        &self,
+        params: TypeHierarchySupertypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let Some(data) = params
+            .item
+            .data
+            .and_then(|d| serde_json::from_value::<TypeHierarchyData>(d).ok())
         else {
             return Ok(None);
         };
 
-        let uri = Arc::new(params.text_document.uri);
+        // Only `redef`s have a supertype, the type they extend.
+        if !data.redef {
+            return Ok(Some(Vec::new()));
+        }
+
         let state = self.state.read().await;
-        let Some(missing) = state.parse(Arc::clone(&uri)).and_then(|t| {
-            t.root_node().errors().into_iter().find_map(|err| {
-                // Filter out `MISSING` nodes at the diagnostic.
-                if err.is_missing() && err.range() == diag.range {
-                    // `kind` holds the fix for the `MISSING` error.
-                    Some(err.kind().to_string())
-                } else {
-                    None
-                }
-            })
-        }) else {
+
+        Ok(Some(
+            type_decls(&state, &data.fqid)
+                .iter()
+                .filter(|d| !is_type_redef(d))
+                .filter_map(type_hierarchy_item)
+                .collect(),
+        ))
+    }
+
+    #[instrument]
+    async fn subtypes(
+        &self,
+        params: TypeHierarchySubtypesParams,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>> {
+        let Some(data) = params
+            .item
+            .data
+            .and_then(|d| serde_json::from_value::<TypeHierarchyData>(d).ok())
+        else {
             return Ok(None);
         };
 
-        let edit = Some(WorkspaceEdit::new(
-            [(
-                (*uri).clone(),
-                vec![{ TextEdit::new(diag.range, missing.clone()) }],
-            )]
-            .into_iter()
-            .collect(),
-        ));
-
-        Ok(Some(CodeActionResponse::from(vec![CodeAction {
-            title: format!("Insert missing '{missing}'"),
-            kind: Some(CodeActionKind::QUICKFIX),
-            edit,
-            ..CodeAction::default()
+        // The subtypes of a type are the `redef`s contributing to it.
+        if data.redef {
+            return Ok(Some(Vec::new()));
         }
-        .into()])))
+
+        let state = self.state.read().await;
+
+        Ok(Some(
+            type_decls(&state, &data.fqid)
+                .iter()
+                .filter(|d| is_type_redef(d))
+                .filter_map(type_hierarchy_item)
+                .collect(),
+        ))
     }
 
-    #[allow(clippy::too_many_lines)]
     #[instrument]
-    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
         let uri = Arc::new(params.text_document.uri);
-        let range = params.range;
+        let state = self.state.read().await;
 
-        let mut hints = Vec::new();
+        let mut actions = Vec::new();
 
-        let state = self.state.read().await;
+        // Offer all fixes for diagnostics in the requested range.
+        for diag in params
+            .context
+            .diagnostics
+            .iter()
+            .filter(|d| d.range.start <= params.range.end && params.range.start <= d.range.end)
+        {
+            for fix in fix::fixes(&state, &uri, diag) {
+                actions.push(
+                    CodeAction {
+                        title: fix.title,
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diag.clone()]),
+                        edit: Some(WorkspaceEdit::new(
+                            [((*uri).clone(), fix.edits)].into_iter().collect(),
+                        )),
+                        ..CodeAction::default()
+                    }
+                    .into(),
+                );
+            }
+        }
 
-        let params = if state.initialization_options().inlay_hints_parameters {
-            #[allow(clippy::redundant_clone)] // Used cloned iter for ownership with `tokio::spawn`
-            state
-                .function_calls(Arc::clone(&uri))
-                .iter()
-                .filter(|c| c.f.range.start >= range.start && c.f.range.end <= range.end)
-                .cloned()
-                .map(|c| {
-                    let state = state.snapshot();
-                    tokio::spawn(async move {
-                        match &state.resolve(c.f.clone())?.kind {
-                            DeclKind::FuncDef(s)
-                            | DeclKind::FuncDecl(s)
-                            | DeclKind::HookDef(s)
-                            | DeclKind::HookDecl(s)
-                            | DeclKind::EventDef(s)
-                            | DeclKind::EventDecl(s) => Some(
-                                c.args
-                                    .into_iter()
-                                    .zip(s.args.iter())
-                                    .filter_map(|(p, a)| {
-                                        // If the argument has the same name as the parameter do
-                                        // not set an inlay hint.
-                                        let uri = p.uri;
-                                        let tree = state.parse(Arc::clone(&uri))?;
-                                        let node = tree
-                                            .root_node()
-                                            .named_descendant_for_point_range(p.range)?;
-                                        let source = state.source(uri)?;
-                                        let maybe_id = node.utf8_text(source.as_bytes()).ok()?;
-                                        if maybe_id == a.id {
-                                            return None;
-                                        }
+        // Offer extracting the selection into a function or local.
+        if params.range.start != params.range.end {
+            for (title, edits) in [
+                (
+                    "Extract function",
+                    refactor::extract_function(&state, &uri, params.range),
+                ),
+                (
+                    "Extract local",
+                    refactor::extract_local(&state, &uri, params.range),
+                ),
+            ] {
+                let Some(edits) = edits else {
+                    continue;
+                };
 
-                                        Some(InlayHint {
-                                            position: p.range.start,
-                                            label: InlayHintLabel::String(format!("{}:", a.id)),
-                                            kind: Some(InlayHintKind::PARAMETER),
-                                            text_edits: None,
-                                            tooltip: Some(InlayHintTooltip::MarkupContent(
-                                                MarkupContent {
-                                                    kind: MarkupKind::Markdown,
-                                                    value: a.documentation.to_string(),
-                                                },
-                                            )),
-                                            padding_left: None,
-                                            padding_right: Some(true),
-                                            data: None,
-                                        })
-                                    })
-                                    .collect::<Vec<_>>(),
+                actions.push(
+                    CodeAction {
+                        title: title.to_string(),
+                        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+                        edit: Some(WorkspaceEdit::new(
+                            [((*uri).clone(), edits)].into_iter().collect(),
+                        )),
+                        ..CodeAction::default()
+                    }
+                    .into(),
+                );
+            }
+        }
+
+        // Offer inlining the variable or constant under the cursor.
+        if let Some((title, edits)) = refactor::inline(&state, &uri, params.range.start) {
+            actions.push(
+                CodeAction {
+                    title,
+                    kind: Some(CodeActionKind::REFACTOR_INLINE),
+                    edit: Some(WorkspaceEdit::new(
+                        [((*uri).clone(), edits)].into_iter().collect(),
+                    )),
+                    ..CodeAction::default()
+                }
+                .into(),
+            );
+        }
+
+        // Offer generating handlers for events or hooks under the cursor.
+        for decl in handler_candidates(&state, &uri, params.range.start) {
+            let Some((what, _)) = typecheck::signature(&decl) else {
+                continue;
+            };
+
+            for priority in [None, Some(0)] {
+                let Some(edit) = handler_stub(&state, &uri, &decl, priority) else {
+                    continue;
+                };
+
+                let with_priority = if priority.is_some() {
+                    " with &priority"
+                } else {
+                    ""
+                };
+
+                actions.push(
+                    CodeAction {
+                        title: format!(
+                            "Generate handler for {what} '{}'{with_priority}",
+                            decl.fqid
+                        ),
+                        kind: Some(CodeActionKind::REFACTOR),
+                        edit: Some(edit),
+                        ..CodeAction::default()
+                    }
+                    .into(),
+                );
+            }
+        }
+
+        if actions.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(actions))
+    }
+
+    #[instrument]
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
+        match params.command.as_str() {
+            COMMAND_GENERATE_HANDLER => {
+                // Arguments are the URI of the file to add the handler to, the fully-qualified ID
+                // of the event or hook, and an optional priority.
+                let mut args = params.arguments.into_iter();
+                let (Some(Ok(uri)), Some(Ok(fqid))) = (
+                    args.next().map(serde_json::from_value::<Url>),
+                    args.next().map(serde_json::from_value::<String>),
+                ) else {
+                    return Err(Error::invalid_params(
+                        "expected URI and ID of event or hook as arguments",
+                    ));
+                };
+                let priority = args.next().and_then(|p| serde_json::from_value(p).ok());
+
+                let edit = {
+                    let state = self.state.read().await;
+                    let uri = Arc::new(uri);
+
+                    let decl = state
+                        .files()
+                        .iter()
+                        .find_map(|f| {
+                            state
+                                .decls(Arc::clone(f))
+                                .iter()
+                                .find(|d| {
+                                    d.fqid == fqid
+                                        && matches!(
+                                            d.kind,
+                                            DeclKind::EventDecl(_) | DeclKind::HookDecl(_)
+                                        )
+                                })
+                                .cloned()
+                        })
+                        .ok_or_else(|| {
+                            Error::invalid_params(format!("unknown event or hook '{fqid}'"))
+                        })?;
+
+                    handler_stub(&state, &uri, &decl, priority).ok_or_else(Error::internal_error)?
+                };
+
+                if let Some(client) = &self.client {
+                    if let Err(e) = client.apply_edit(edit.clone()).await {
+                        error!("could not apply edit: {e}");
+                    }
+                }
+
+                Ok(Some(
+                    serde_json::to_value(edit).map_err(|_| Error::internal_error())?,
+                ))
+            }
+            COMMAND_BTEST_RUN | COMMAND_BTEST_UPDATE_BASELINE => {
+                // The argument is the URI of the test to run.
+                let Some(Ok(uri)) = params
+                    .arguments
+                    .into_iter()
+                    .next()
+                    .map(serde_json::from_value::<Url>)
+                else {
+                    return Err(Error::invalid_params("expected URI of test as argument"));
+                };
+
+                let update_baseline = params.command == COMMAND_BTEST_UPDATE_BASELINE;
+                self.run_btest(uri, update_baseline).await.map(Some)
+            }
+            COMMAND_LOG_SCHEMA => {
+                // Arguments are the output format, `json` (default) or `markdown`, and an
+                // optional URI of a script to consider the logs of. Without a URI the logs of all
+                // known files are extracted.
+                let mut args = params.arguments.into_iter();
+                let format = match args.next().map(serde_json::from_value::<String>) {
+                    None => logs::Format::Json,
+                    Some(Ok(format)) => format.parse().map_err(Error::invalid_params)?,
+                    Some(Err(_)) => return Err(Error::invalid_params("expected format as string")),
+                };
+                let uri = args
+                    .next()
+                    .map(serde_json::from_value::<Url>)
+                    .transpose()
+                    .map_err(|_| Error::invalid_params("expected URI of a script"))?;
+
+                let state = self.state.read().await;
+                let files = match uri {
+                    Some(uri) => logs::loaded_script_set(&state, &[Arc::new(uri)]),
+                    None => state.files().to_vec(),
+                };
+                let streams = logs::streams(&state, &files);
+
+                Ok(Some(match format {
+                    logs::Format::Json => {
+                        serde_json::to_value(streams).map_err(|_| Error::internal_error())?
+                    }
+                    logs::Format::Markdown => Value::String(
+                        logs::render(&streams, format).map_err(|_| Error::internal_error())?,
+                    ),
+                }))
+            }
+            _ => Err(Error::invalid_params(format!(
+                "unknown command '{}'",
+                params.command
+            ))),
+        }
+    }
+
+    #[instrument]
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+
+        let state = self.state.read().await;
+
+        let Some(source) = state.source(Arc::new(uri.clone())) else {
+            return Ok(None);
+        };
+
+        let mut lenses = count_lenses(&state, &Arc::new(uri.clone()));
+
+        // Only offer to run tests which are part of a btest configuration.
+        if let Some(command) = btest::commands(&source).into_iter().next() {
+            let is_test = match uri.to_file_path() {
+                Ok(f) => btest::find_config(&f).await.is_some_and(|c| c.contains(&f)),
+                Err(()) => false,
+            };
+
+            if is_test {
+                let range = Range::new(
+                    Position::new(command.line, 0),
+                    Position::new(command.line, 0),
+                );
+
+                lenses.extend(
+                    [
+                        ("Run test", COMMAND_BTEST_RUN),
+                        ("Update baseline", COMMAND_BTEST_UPDATE_BASELINE),
+                    ]
+                    .into_iter()
+                    .map(|(title, command)| CodeLens {
+                        range,
+                        command: Some(Command::new(
+                            title.into(),
+                            command.into(),
+                            Some(vec![json!(uri)]),
+                        )),
+                        data: None,
+                    }),
+                );
+            }
+        }
+
+        Ok(Some(lenses))
+    }
+
+    #[instrument]
+    async fn code_lens_resolve(&self, lens: CodeLens) -> Result<CodeLens> {
+        let Some(data) = lens
+            .data
+            .clone()
+            .and_then(|d| serde_json::from_value::<LensData>(d).ok())
+        else {
+            return Ok(lens);
+        };
+
+        let state = self.state.read().await;
+
+        let uri = Arc::new(data.uri.clone());
+        let Some(decl) = state
+            .decls(Arc::clone(&uri))
+            .iter()
+            .find(|d| {
+                d.loc
+                    .as_ref()
+                    .is_some_and(|l| l.selection_range == data.range)
+            })
+            .cloned()
+        else {
+            return Ok(lens);
+        };
+
+        let locations = match data.kind {
+            LensKind::Handlers => implementations(&state, &decl),
+            LensKind::Redefs => redefs(&state, &decl),
+            LensKind::References => references(&state, Arc::new(decl))
+                .await
+                .into_iter()
+                // Do not count the declaration itself.
+                .filter(|l| !(l.uri == uri && l.range == data.range))
+                .sorted_by(|a, b| (&a.uri, a.range.start).cmp(&(&b.uri, b.range.start)))
+                .map(|l| Location::new((*l.uri).clone(), l.range))
+                .collect(),
+        };
+
+        Ok(CodeLens {
+            command: Some(Command::new(
+                data.kind.title(locations.len()),
+                COMMAND_SHOW_LOCATIONS.into(),
+                Some(vec![
+                    json!(data.uri),
+                    json!(data.range.start),
+                    json!(locations),
+                ]),
+            )),
+            ..lens
+        })
+    }
+
+    #[allow(clippy::too_many_lines)]
+    #[instrument]
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let uri = Arc::new(params.text_document.uri);
+        let range = params.range;
+
+        let mut hints = Vec::new();
+
+        let state = self.state.read().await;
+
+        let params = if state.initialization_options().inlay_hints_parameters {
+            #[allow(clippy::redundant_clone)] // Used cloned iter for ownership with `tokio::spawn`
+            state
+                .function_calls(Arc::clone(&uri))
+                .iter()
+                .filter(|c| c.f.range.start >= range.start && c.f.range.end <= range.end)
+                .cloned()
+                .map(|c| {
+                    let state = state.snapshot();
+                    tokio::spawn(async move {
+                        match &state.resolve(c.f.clone())?.kind {
+                            DeclKind::FuncDef(s)
+                            | DeclKind::FuncDecl(s)
+                            | DeclKind::HookDef(s)
+                            | DeclKind::HookDecl(s)
+                            | DeclKind::EventDef(s)
+                            | DeclKind::EventDecl(s) => Some(
+                                c.args
+                                    .into_iter()
+                                    .zip(s.args.iter())
+                                    .filter_map(|(p, a)| {
+                                        // If the argument has the same name as the parameter do
+                                        // not set an inlay hint.
+                                        let uri = p.uri;
+                                        let tree = state.parse(Arc::clone(&uri))?;
+                                        let node = tree
+                                            .root_node()
+                                            .named_descendant_for_point_range(p.range)?;
+                                        let source = state.source(uri)?;
+                                        let maybe_id = node.utf8_text(source.as_bytes()).ok()?;
+                                        if maybe_id == a.id {
+                                            return None;
+                                        }
+
+                                        Some(InlayHint {
+                                            position: p.range.start,
+                                            label: InlayHintLabel::String(format!("{}:", a.id)),
+                                            kind: Some(InlayHintKind::PARAMETER),
+                                            text_edits: None,
+                                            tooltip: Some(InlayHintTooltip::MarkupContent(
+                                                MarkupContent {
+                                                    kind: MarkupKind::Markdown,
+                                                    value: a.documentation.to_string(),
+                                                },
+                                            )),
+                                            padding_left: None,
+                                            padding_right: Some(true),
+                                            data: None,
+                                        })
+                                    })
+                                    .collect::<Vec<_>>(),
                             ),
                             _ => None,
                         }
@@ -1601,6 +2311,451 @@ impl LanguageServer for Backend {
     }
 }
 
+/// Command to generate a handler stub for an event or hook.
+const COMMAND_GENERATE_HANDLER: &str = "zeek.generateHandler";
+
+/// Command to run a btest test.
+const COMMAND_BTEST_RUN: &str = "zeek.btest.run";
+
+/// Command to run a btest test and update its baseline.
+const COMMAND_BTEST_UPDATE_BASELINE: &str = "zeek.btest.updateBaseline";
+
+/// Command to extract the schemas of all logs.
+const COMMAND_LOG_SCHEMA: &str = "zeek.logSchema";
+
+/// Command to show a list of locations, e.g., handlers of an event.
+///
+/// This command is implemented by clients and invoked with the URI and position the list belongs
+/// to, and the locations to show.
+const COMMAND_SHOW_LOCATIONS: &str = "zeek.showLocations";
+
+/// Find all definitions of a function, event or hook.
+fn implementations(db: &Database, decl: &Decl) -> Vec<Location> {
+    db.files()
+        .iter()
+        .flat_map(|f| db.decls(Arc::clone(f)).iter().cloned().collect::<Vec<_>>())
+        .filter(|d| {
+            matches!(
+                &d.kind,
+                DeclKind::EventDef(_) | DeclKind::FuncDef(_) | DeclKind::HookDef(_)
+            )
+        })
+        .filter_map(|d| {
+            let loc = &d.loc.as_ref()?;
+            if d.id == decl.id {
+                Some(Location::new((*loc.uri).clone(), loc.range))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Find all declarations and `redef`s of a record or enum type, sorted by their location.
+fn type_decls(db: &Database, fqid: &str) -> Vec<Decl> {
+    db.files()
+        .iter()
+        .flat_map(|f| db.decls(Arc::clone(f)).iter().cloned().collect::<Vec<_>>())
+        .filter(|d| {
+            matches!(
+                &d.kind,
+                DeclKind::Type(_)
+                    | DeclKind::Enum(_)
+                    | DeclKind::RedefRecord(_)
+                    | DeclKind::RedefEnum(_)
+            ) && d.fqid == fqid
+        })
+        .sorted_by(|a, b| {
+            let key = |d: &Decl| d.loc.as_ref().map(|l| (Arc::clone(&l.uri), l.range.start));
+            key(a).cmp(&key(b))
+        })
+        .collect()
+}
+
+/// Find all `redef`s of a record or enum type.
+fn redefs(db: &Database, decl: &Decl) -> Vec<Location> {
+    type_decls(db, &decl.fqid)
+        .into_iter()
+        .filter(is_type_redef)
+        .filter_map(|d| {
+            let loc = d.loc.as_ref()?;
+            Some(Location::new((*loc.uri).clone(), loc.range))
+        })
+        .collect()
+}
+
+fn is_type_redef(decl: &Decl) -> bool {
+    matches!(decl.kind, DeclKind::RedefRecord(_) | DeclKind::RedefEnum(_))
+}
+
+/// Data attached to type hierarchy items to find them again in later requests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct TypeHierarchyData {
+    fqid: String,
+
+    /// Whether the item is a `redef` of the type.
+    redef: bool,
+}
+
+/// Create a type hierarchy item for the declaration or a `redef` of a record or enum type.
+fn type_hierarchy_item(decl: &Decl) -> Option<TypeHierarchyItem> {
+    let (kind, detail) = match decl.kind {
+        DeclKind::Type(_) => (SymbolKind::STRUCT, "record"),
+        DeclKind::Enum(_) => (SymbolKind::ENUM, "enum"),
+        DeclKind::RedefRecord(_) => (SymbolKind::STRUCT, "redef record"),
+        DeclKind::RedefEnum(_) => (SymbolKind::ENUM, "redef enum"),
+        _ => return None,
+    };
+
+    let loc = decl.loc.as_ref()?;
+
+    Some(TypeHierarchyItem {
+        name: decl.fqid.to_string(),
+        kind,
+        tags: None,
+        detail: Some(detail.into()),
+        uri: (*loc.uri).clone(),
+        range: loc.range,
+        selection_range: loc.selection_range,
+        data: Some(json!(TypeHierarchyData {
+            fqid: decl.fqid.to_string(),
+            redef: is_type_redef(decl),
+        })),
+    })
+}
+
+/// Render the fields of a record grouped by the file contributing them.
+fn record_fields(db: &Database, fields: &[Decl]) -> String {
+    let mut files: Vec<(Option<&Url>, Vec<&Decl>)> = Vec::new();
+    for field in fields {
+        let uri = field.loc.as_ref().map(|l| l.uri.as_ref());
+        match files.iter_mut().find(|(u, _)| *u == uri) {
+            Some((_, fields)) => fields.push(field),
+            None => files.push((uri, vec![field])),
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|(uri, fields)| {
+            let fields = fields
+                .into_iter()
+                .map(|f| match FieldInfo::new(db, f).detail() {
+                    detail if detail.is_empty() => format!("- `{}`", f.id),
+                    detail => format!("- `{}: {detail}`", f.id),
+                })
+                .join("\n");
+
+            match uri {
+                Some(uri) => format!("Fields from `{}`:\n{fields}", uri.path()),
+                None => format!("Fields:\n{fields}"),
+            }
+        })
+        .join("\n\n")
+}
+
+/// Where a record field comes from and how it is declared.
+pub(crate) struct FieldInfo {
+    /// Declared type of the field.
+    pub(crate) typ: Option<Str>,
+
+    /// File declaring the field, relative to a prefix or package if possible.
+    pub(crate) file: Option<String>,
+
+    pub(crate) module: ModuleId,
+
+    /// Whether the field was added with `redef record`.
+    pub(crate) redef: bool,
+
+    /// Attributes relevant for users of the field, e.g., `&optional` or `&default=0`.
+    pub(crate) attrs: Vec<String>,
+
+    pub(crate) optional: bool,
+}
+
+impl FieldInfo {
+    pub(crate) fn new(db: &Database, field: &Decl) -> Self {
+        let redef = field.loc.as_ref().is_some_and(|loc| {
+            db.decls(Arc::clone(&loc.uri)).iter().any(|d| {
+                matches!(d.kind, DeclKind::RedefRecord(_))
+                    && d.loc.as_ref().is_some_and(|l| {
+                        l.range.start <= loc.range.start && loc.range.end <= l.range.end
+                    })
+            })
+        });
+
+        let attrs = field
+            .attrs
+            .iter()
+            .filter(|a| matches!(a.name.as_str(), "optional" | "default" | "log"))
+            .map(|a| match &a.value {
+                Some(value) => format!("&{}={value}", a.name),
+                None => format!("&{}", a.name),
+            })
+            .collect();
+
+        Self {
+            typ: db.typ(Arc::new(field.clone())).map(|t| t.fqid.clone()),
+            file: field.loc.as_ref().map(|l| display_path(db, &l.uri)),
+            module: field.module.clone(),
+            redef,
+            attrs,
+            optional: field.attrs.iter().any(|a| a.name == "optional"),
+        }
+    }
+
+    /// Render the type and attributes of the field, e.g., `count &optional`.
+    pub(crate) fn detail(&self) -> String {
+        self.typ
+            .iter()
+            .map(ToString::to_string)
+            .chain(self.attrs.iter().cloned())
+            .join(" ")
+    }
+
+    /// Render the provenance of the field as markdown.
+    pub(crate) fn documentation(&self) -> String {
+        let mut docs = Vec::new();
+
+        if let Some(file) = &self.file {
+            let what = if self.redef {
+                "Added by `redef record` in"
+            } else {
+                "Declared in"
+            };
+
+            docs.push(match &self.module {
+                ModuleId::String(module) => format!("{what} `{file}` (module `{module}`)"),
+                ModuleId::Global | ModuleId::None => format!("{what} `{file}`"),
+            });
+        }
+
+        if self.optional {
+            docs.push(OPTIONAL_FIELD_HINT.to_string());
+        }
+
+        docs.join("\n\n")
+    }
+}
+
+/// Hint shown for `&optional` record fields.
+pub(crate) const OPTIONAL_FIELD_HINT: &str =
+    "Optional field, check whether it is set with `?$` before accessing it.";
+
+/// Render the path of a file relative to the Zeek prefix or package containing it.
+fn display_path(db: &Database, uri: &Url) -> String {
+    let Ok(path) = uri.to_file_path() else {
+        return uri.to_string();
+    };
+
+    db.prefixes()
+        .iter()
+        .find_map(|p| path.strip_prefix(p).ok().map(Path::to_path_buf))
+        .or_else(|| {
+            db.packages().iter().find_map(|p| {
+                let rel = path.strip_prefix(&p.dir).ok()?;
+                Some(Path::new(p.name.as_str()).join(rel))
+            })
+        })
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// What a count lens on a declaration counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum LensKind {
+    Handlers,
+    Redefs,
+    References,
+}
+
+impl LensKind {
+    fn title(self, count: usize) -> String {
+        let what = match self {
+            Self::Handlers => "handler",
+            Self::Redefs => "redef",
+            Self::References => "reference",
+        };
+
+        format!("{count} {what}{}", if count == 1 { "" } else { "s" })
+    }
+}
+
+/// Data attached to count lenses so they can be resolved lazily.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct LensData {
+    uri: Url,
+
+    /// Selection range of the declaration the lens is on.
+    range: Range,
+
+    kind: LensKind,
+}
+
+/// Compute unresolved count lenses for declarations of events, hooks and record types in a file.
+fn count_lenses(db: &Database, uri: &Arc<Url>) -> Vec<CodeLens> {
+    let references = db.initialization_options().references;
+
+    db.decls(Arc::clone(uri))
+        .iter()
+        .filter_map(|d| {
+            let loc = d.loc.as_ref().filter(|l| l.uri == *uri)?;
+
+            let kinds: &[LensKind] = match (&d.kind, references) {
+                (DeclKind::EventDecl(_) | DeclKind::HookDecl(_), true) => {
+                    &[LensKind::Handlers, LensKind::References]
+                }
+                (DeclKind::EventDecl(_) | DeclKind::HookDecl(_), false) => &[LensKind::Handlers],
+                (DeclKind::Type(_), true) => &[LensKind::Redefs, LensKind::References],
+                (DeclKind::Type(_), false) => &[LensKind::Redefs],
+                _ => return None,
+            };
+
+            Some(
+                kinds
+                    .iter()
+                    .map(|kind| CodeLens {
+                        range: loc.selection_range,
+                        command: None,
+                        data: Some(json!(LensData {
+                            uri: (**uri).clone(),
+                            range: loc.selection_range,
+                            kind: *kind,
+                        })),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .flatten()
+        .collect()
+}
+
+/// Find event or hook declarations which could be referenced by the identifier at the given
+/// position.
+fn handler_candidates(db: &Database, uri: &Arc<Url>, position: Position) -> Vec<Decl> {
+    let Some(tree) = db.parse(Arc::clone(uri)) else {
+        return Vec::new();
+    };
+    let Some(source) = db.source(Arc::clone(uri)) else {
+        return Vec::new();
+    };
+
+    let Some(node) = tree
+        .root_node()
+        .named_descendant_for_position(position)
+        .filter(|n| n.kind() == "id")
+    else {
+        return Vec::new();
+    };
+
+    let is_handler_decl =
+        |d: &Decl| matches!(d.kind, DeclKind::EventDecl(_) | DeclKind::HookDecl(_));
+
+    // Prefer the decl the identifier resolves to.
+    if let Some(decl) = db
+        .resolve(NodeLocation::from_node(Arc::clone(uri), node))
+        .filter(|d| is_handler_decl(d))
+    {
+        return vec![(*decl).clone()];
+    }
+
+    // Else search all known files for a matching declaration.
+    let Ok(id) = node.utf8_text(source.as_bytes()) else {
+        return Vec::new();
+    };
+
+    db.files()
+        .iter()
+        .flat_map(|f| {
+            db.decls(Arc::clone(f))
+                .iter()
+                .filter(|d| is_handler_decl(d) && (d.fqid == id || d.id == id))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unique()
+        .collect()
+}
+
+/// Compute an edit adding a stub handler for the event or hook declared in `decl` to a file.
+///
+/// If the file declaring the event or hook is not loaded yet an `@load` for it is added as well.
+fn handler_stub(
+    db: &Database,
+    uri: &Arc<Url>,
+    decl: &Decl,
+    priority: Option<i32>,
+) -> Option<WorkspaceEdit> {
+    let (what, _) = typecheck::signature(decl)?;
+    if !matches!(decl.kind, DeclKind::EventDecl(_) | DeclKind::HookDecl(_)) {
+        return None;
+    }
+
+    let source = db.source(Arc::clone(uri))?;
+    let params = typecheck::declared_params(db, decl)?;
+
+    let priority = priority
+        .map(|p| format!(" &priority={p}"))
+        .unwrap_or_default();
+    let separator = if source.is_empty() {
+        ""
+    } else if source.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    let stub = format!(
+        "{separator}{what} {}({params}){priority}\n\t{{\n\t}}\n",
+        decl.fqid
+    );
+
+    let end =
+        line_index::LineIndex::new(&source).line_col(u32::try_from(source.len()).ok()?.into());
+    let end = Position::new(end.line, end.col);
+
+    let mut edits = Vec::new();
+
+    if let Some(load) = missing_load(db, uri, decl) {
+        // Add the load after any existing loads.
+        let line = source
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| l.trim_start().starts_with("@load"))
+            .map(|(i, _)| i + 1)
+            .max()
+            .unwrap_or_default();
+        let position = Position::new(u32::try_from(line).ok()?, 0);
+
+        edits.push(TextEdit::new(
+            Range::new(position, position),
+            format!("@load {load}\n"),
+        ));
+    }
+
+    edits.push(TextEdit::new(Range::new(end, end), stub));
+
+    Some(WorkspaceEdit::new(
+        [((**uri).clone(), edits)].into_iter().collect(),
+    ))
+}
+
+/// If `decl` is not visible from `uri` compute the `@load` needed to make it visible.
+fn missing_load(db: &Database, uri: &Arc<Url>, decl: &Decl) -> Option<Str> {
+    let loc = decl.loc.as_ref()?;
+
+    if loc.uri == *uri
+        || db.implicit_decls().contains(decl)
+        || db.explicit_decls_recursive(Arc::clone(uri)).contains(decl)
+    {
+        return None;
+    }
+
+    let path = uri.to_file_path().ok()?;
+    file_to_load(&loc.uri, path.parent()?, &db.prefixes())
+}
+
 fn word_at_position(source: &str, position: Position) -> Option<Str> {
     let line = source.lines().nth(usize::try_from(position.line).ok()?)?;
     let (a, b) = line.split_at(usize::try_from(position.character + 1).ok()?);
@@ -1630,6 +2785,10 @@ fn fuzzy_search_symbol(db: &Database, symbol: &str) -> Vec<(f32, Decl)> {
         .collect()
 }
 
+fn to_symbol_tags(decl: &Decl) -> Option<Vec<SymbolTag>> {
+    decl.deprecation().map(|_| vec![SymbolTag::DEPRECATED])
+}
+
 fn to_symbol_kind(kind: &DeclKind) -> SymbolKind {
     match kind {
         DeclKind::Global | DeclKind::Variable | DeclKind::Redef | DeclKind::Index(_, _) => {
@@ -1649,6 +2808,37 @@ fn to_symbol_kind(kind: &DeclKind) -> SymbolKind {
     }
 }
 
+async fn status(backend: &Arc<Backend>) -> Result<Value> {
+    backend.status().await
+}
+
+/// Parameters of the custom `zeek/enumMembers` request.
+#[derive(Debug, Deserialize)]
+pub struct EnumMembersParams {
+    /// Fully-qualified ID of the enum, e.g., `Notice::Type`.
+    #[serde(rename = "enum")]
+    pub id: String,
+}
+
+/// A member of an enum as returned by the custom `zeek/enumMembers` request.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct EnumMember {
+    pub id: String,
+
+    /// Location of the member; the file contributing the member.
+    pub location: Option<Location>,
+
+    /// Zeekygen documentation of the member.
+    pub documentation: String,
+}
+
+async fn enum_members(
+    backend: &Arc<Backend>,
+    params: EnumMembersParams,
+) -> Result<Vec<EnumMember>> {
+    backend.enum_members(params).await
+}
+
 pub async fn run() {
     let (tx, rx) = std::sync::mpsc::channel::<notify::Result<notify::Event>>();
     let file_watcher = match notify::recommended_watcher(tx) {
@@ -1659,13 +2849,16 @@ pub async fn run() {
         }
     };
 
-    let (service, socket) = LspService::new(|client| {
+    let (service, socket) = LspService::build(|client| {
         Arc::new(Backend {
             client: Some(client),
             file_watcher: file_watcher.map(|w| tokio::sync::Mutex::new(w)),
             ..Backend::default()
         })
-    });
+    })
+    .custom_method("zeek/status", status)
+    .custom_method("zeek/enumMembers", enum_members)
+    .finish();
 
     let service_weak = Arc::downgrade(service.inner());
     tokio::spawn(async move {
@@ -1701,7 +2894,7 @@ pub async fn run() {
 }
 
 #[allow(clippy::struct_excessive_bools)]
-#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
 /// Custom `initializationOptions` clients can send.
 pub struct InitializationOptions {
     #[serde(default = "InitializationOptions::_default_check_for_updates")]
@@ -1727,10 +2920,25 @@ pub struct InitializationOptions {
 
     #[serde(default = "InitializationOptions::_debug_ast_nodes")]
     debug_ast_nodes: bool,
+
+    #[serde(default)]
+    zeek_version: Option<String>,
+
+    #[serde(default)]
+    defines: Vec<String>,
+
+    #[serde(default = "InitializationOptions::_default_bare_mode")]
+    bare_mode: bool,
+
+    #[serde(default = "InitializationOptions::_default_unused_handler_parameters")]
+    pub(crate) unused_handler_parameters: bool,
+
+    #[serde(default)]
+    pub(crate) lints: BTreeMap<String, lint::Level>,
 }
 
 impl InitializationOptions {
-    const fn new() -> Self {
+    pub(crate) const fn new() -> Self {
         Self {
             check_for_updates: true,
             inlay_hints_variables: true,
@@ -1740,62 +2948,103 @@ impl InitializationOptions {
             semantic_highlighting: true,
             debug_ast_nodes: false,
             enable_filewatcher: false,
+            zeek_version: None,
+            defines: Vec::new(),
+            bare_mode: false,
+            unused_handler_parameters: false,
+            lints: BTreeMap::new(),
         }
     }
 
-    const fn _default_check_for_updates() -> bool {
+    fn _default_bare_mode() -> bool {
+        Self::new().bare_mode
+    }
+
+    fn _default_check_for_updates() -> bool {
         Self::new().check_for_updates
     }
 
-    const fn _default_enable_file_watcher() -> bool {
+    fn _default_enable_file_watcher() -> bool {
         Self::new().enable_filewatcher
     }
 
-    const fn _default_inlay_hints_parameters() -> bool {
+    fn _default_inlay_hints_parameters() -> bool {
         Self::new().inlay_hints_parameters
     }
 
-    const fn _default_inlay_hints_variables() -> bool {
+    fn _default_inlay_hints_variables() -> bool {
         Self::new().inlay_hints_variables
     }
 
-    const fn _default_references() -> bool {
+    fn _default_references() -> bool {
         Self::new().references
     }
 
-    const fn _default_rename() -> bool {
+    fn _default_rename() -> bool {
         Self::new().rename
     }
 
-    const fn _semantic_highlighting() -> bool {
+    fn _semantic_highlighting() -> bool {
         Self::new().semantic_highlighting
     }
 
-    const fn _debug_ast_nodes() -> bool {
+    fn _debug_ast_nodes() -> bool {
         Self::new().debug_ast_nodes
     }
+
+    fn _default_unused_handler_parameters() -> bool {
+        Self::new().unused_handler_parameters
+    }
 }
 
-const ERROR_CODE_IS_MISSING: i32 = 1;
+pub(crate) const ERROR_CODE_IS_MISSING: i32 = 1;
+
+/// Get semantic diagnostics for a file.
+fn file_diagnostics(db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+    lint::diagnostics(db, Arc::clone(uri))
+}
 
-/// Extracts all errors in a AST.
-fn tree_diagnostics(tree: &query::Node) -> Vec<Diagnostic> {
-    tree.errors()
+/// Get diagnostics of files embedded into a btest test, relative to the test.
+fn btest_embedded_diagnostics(db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+    let Some(source) = db.source(Arc::clone(uri)) else {
+        return Vec::new();
+    };
+
+    if !btest::is_test(&source) {
+        return Vec::new();
+    }
+
+    btest::embedded_files(&source)
         .into_iter()
-        .map(|err| {
-            let code = if err.is_missing() {
-                Some(ERROR_CODE_IS_MISSING)
-            } else {
-                None
+        .filter_map(|f| {
+            let embedded = Arc::new(btest::embedded_uri(uri, &f.name)?);
+            let tree = db.parse(Arc::clone(&embedded))?;
+            let source = db.source(Arc::clone(&embedded))?;
+
+            let mut diags = syntax::diagnostics(tree.root_node(), &source);
+            diags.extend(file_diagnostics(db, &embedded));
+
+            for d in &mut diags {
+                d.range.start.line += f.start_line;
+                d.range.end.line += f.start_line;
             }
-            .map(NumberOrString::Number);
 
+            Some(diags)
+        })
+        .flatten()
+        .collect()
+}
+
+fn sig_diagnostics(file: &sig::File) -> Vec<Diagnostic> {
+    file.errors
+        .iter()
+        .map(|e| {
             Diagnostic::new(
-                err.range(),
-                Some(DiagnosticSeverity::WARNING),
-                code,
+                e.range,
+                Some(DiagnosticSeverity::ERROR),
+                None,
                 None,
-                err.error().to_string(),
+                e.message.clone(),
                 None,
                 None,
             )
@@ -1803,6 +3052,116 @@ fn tree_diagnostics(tree: &query::Node) -> Vec<Diagnostic> {
         .collect()
 }
 
+fn sig_symbols(file: &sig::File) -> Vec<DocumentSymbol> {
+    file.signatures
+        .iter()
+        .map(|s| {
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name: s.id.to_string(),
+                detail: None,
+                kind: SymbolKind::OBJECT,
+                tags: None,
+                deprecated: None,
+                range: s.range,
+                selection_range: s.selection_range,
+                children: None,
+            }
+        })
+        .collect()
+}
+
+/// Get the Zeek scripts loading a signature file with `@load-sigs`.
+fn sig_loaders(db: &Database, uri: &Url) -> Vec<Arc<Url>> {
+    let Ok(path) = uri.to_file_path() else {
+        return Vec::new();
+    };
+
+    db.files()
+        .iter()
+        .filter(|f| !sig::is_sig_file(f))
+        .filter(|f| {
+            let (Some(source), Ok(file)) = (db.source(Arc::clone(f)), f.to_file_path()) else {
+                return false;
+            };
+
+            sig::load_sigs(&source).into_iter().any(|load| {
+                let mut load = PathBuf::from(load);
+                if load.extension().is_none() {
+                    load.set_extension("sig");
+                }
+
+                if load.starts_with(".") || load.starts_with("..") {
+                    file.parent()
+                        .is_some_and(|dir| dir.join(&load).clean() == path)
+                } else {
+                    path.ends_with(&load)
+                }
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Find the definition of the entity at the position in a signature file.
+///
+/// Functions in `eval` conditions are resolved from the scripts loading the signature file, and
+/// signatures in `requires-signature` from all known signature files.
+fn sig_definition(
+    db: &Database,
+    uri: &Arc<Url>,
+    file: &sig::File,
+    position: Position,
+) -> Option<Location> {
+    let attr = file.attribute_at(position)?;
+    let value = attr.value()?;
+
+    match attr.name.text.as_str() {
+        "eval" => {
+            let decl = sig_loaders(db, uri)
+                .into_iter()
+                .find_map(|loader| {
+                    db.resolve_id(
+                        value.text.clone(),
+                        NodeLocation::from_range(loader, Range::default()),
+                    )
+                })
+                // Fall back to searching all functions if no script loads this file.
+                .or_else(|| {
+                    db.files().iter().find_map(|f| {
+                        db.decls(Arc::clone(f))
+                            .iter()
+                            .find(|d| {
+                                d.fqid == value.text
+                                    && matches!(
+                                        d.kind,
+                                        DeclKind::FuncDecl(_) | DeclKind::FuncDef(_)
+                                    )
+                            })
+                            .cloned()
+                            .map(Arc::new)
+                    })
+                })?;
+
+            let loc = decl.loc.as_ref()?;
+            Some(Location::new((*loc.uri).clone(), loc.range))
+        }
+        "requires-signature" | "requires-reverse-signature" => {
+            // Prefer signatures from the same file.
+            std::iter::once(uri).chain(db.files().iter()).find_map(|f| {
+                let s = db
+                    .parse_sig(Arc::clone(f))?
+                    .signatures
+                    .iter()
+                    .find(|s| s.id == value.text)
+                    .map(|s| s.selection_range)?;
+                Some(Location::new((**f).clone(), s))
+            })
+        }
+        _ => None,
+    }
+}
+
 async fn references(db: &Database, decl: Arc<Decl>) -> FxHashSet<NodeLocation> {
     /// Helper to compute all sources reachable from a given file.
     fn all_sources(f: Arc<Url>, db: &Database) -> FxHashSet<Arc<Url>> {
@@ -1837,35 +3196,7 @@ async fn references(db: &Database, decl: Arc<Decl>) -> FxHashSet<NodeLocation> {
                 let db = db.snapshot();
                 let decl = Arc::clone(&decl);
                 let f = Arc::clone(f);
-                tokio::spawn(async move {
-                    Some(
-                        db.ids(f)
-                            .iter()
-                            .filter_map(|loc| {
-                                // Prefilter ids so that they at least somewhere contain the text
-                                // of the decl.
-                                let tree = db.parse(Arc::clone(&loc.uri))?;
-                                let source = db.source(Arc::clone(&loc.uri))?;
-                                let txt = tree
-                                    .root_node()
-                                    .named_descendant_for_point_range(loc.range)?
-                                    .utf8_text(source.as_bytes())
-                                    .ok()?;
-                                if !txt.contains(decl.id.as_str()) {
-                                    return None;
-                                }
-
-                                db.resolve(loc.clone()).and_then(|resolved| {
-                                    if resolved != decl {
-                                        return None;
-                                    }
-
-                                    Some(loc.clone())
-                                })
-                            })
-                            .collect::<Vec<_>>(),
-                    )
-                })
+                tokio::spawn(async move { file_references(&db, f, &decl) })
             })
             .collect();
         futures::future::join_all(locs).await
@@ -1874,7 +3205,34 @@ async fn references(db: &Database, decl: Arc<Decl>) -> FxHashSet<NodeLocation> {
     locs.into_iter()
         .filter_map(std::result::Result::ok)
         .flatten()
-        .flatten()
+        .collect()
+}
+
+/// Find all references to the decl in the given file.
+pub(crate) fn file_references(db: &Database, uri: Arc<Url>, decl: &Decl) -> Vec<NodeLocation> {
+    db.ids(uri)
+        .iter()
+        .filter_map(|loc| {
+            // Prefilter ids so that they at least somewhere contain the text of the decl.
+            let tree = db.parse(Arc::clone(&loc.uri))?;
+            let source = db.source(Arc::clone(&loc.uri))?;
+            let txt = tree
+                .root_node()
+                .named_descendant_for_point_range(loc.range)?
+                .utf8_text(source.as_bytes())
+                .ok()?;
+            if !txt.contains(decl.id.as_str()) {
+                return None;
+            }
+
+            db.resolve(loc.clone()).and_then(|resolved| {
+                if *resolved != *decl {
+                    return None;
+                }
+
+                Some(loc.clone())
+            })
+        })
         .collect()
 }
 
@@ -2041,16 +3399,16 @@ pub(crate) mod test {
     use tower_lsp::{
         lsp_types::{
             ClientCapabilities, CompletionParams, CompletionResponse, DocumentSymbolParams,
-            DocumentSymbolResponse, FormattingOptions, HoverParams, InlayHintParams,
-            PartialResultParams, Position, Range, ReferenceContext, ReferenceParams, RenameParams,
-            SemanticTokensParams, TextDocumentIdentifier, TextDocumentPositionParams, Url,
-            WorkDoneProgressParams, WorkspaceSymbolParams,
+            DocumentSymbolResponse, FormattingOptions, GotoDefinitionResponse, HoverParams,
+            InlayHintParams, Location, PartialResultParams, Position, Range, ReferenceContext,
+            ReferenceParams, RenameParams, SemanticTokensParams, TextDocumentIdentifier,
+            TextDocumentPositionParams, Url, WorkDoneProgressParams, WorkspaceSymbolParams,
         },
         LanguageServer,
     };
     use wiremock::{matchers::method, Mock, MockServer, ResponseTemplate};
 
-    use crate::{ast::Ast, lsp, parse::Parse, zeek, Client};
+    use crate::{ast::Ast, lint, lsp, parse::Parse, query::NodeLocation, zeek, Client, Files};
 
     use super::{Backend, SourceUpdate};
 
@@ -2077,18 +3435,71 @@ pub(crate) mod test {
         }
     }
 
-    pub(crate) fn serve(database: TestDatabase) -> Backend {
-        Backend {
-            state: tokio::sync::RwLock::new(database.0),
-            ..Backend::default()
-        }
+    pub(crate) fn serve(database: TestDatabase) -> Backend {
+        Backend {
+            state: tokio::sync::RwLock::new(database.0),
+            ..Backend::default()
+        }
+    }
+
+    #[test]
+    fn debug_database() {
+        let db = TestDatabase::default();
+
+        assert_eq!(format!("{:?}", db.0), "Database");
+    }
+
+    #[test]
+    fn btest_embedded_files() {
+        let mut db = TestDatabase::default();
+
+        let uri = Arc::new(Url::from_file_path("/t/tests/a.zeek").unwrap());
+        let lib = Arc::new(Url::from_file_path("/t/tests/a.zeek/lib.zeek").unwrap());
+
+        db.add_file(
+            (*uri).clone(),
+            "# @TEST-EXEC: zeek -b %INPUT\n@TEST-START-FILE lib.zeek\nglobal x = 1;\n@TEST-END-FILE\n",
+        );
+
+        assert!(db.0.files().contains(&lib));
+        assert_eq!(
+            db.0.source(Arc::clone(&lib)).as_deref(),
+            Some("global x = 1;\n")
+        );
+        assert!(!db.0.source(Arc::clone(&uri)).unwrap().contains("global x"));
+
+        // Embedded files are removed with the test.
+        db.add_file((*uri).clone(), "# @TEST-EXEC: zeek -b %INPUT\n");
+        assert!(!db.0.files().contains(&lib));
     }
 
     #[test]
-    fn debug_database() {
-        let db = TestDatabase::default();
+    fn btest_embedded_load() {
+        let mut db = TestDatabase::default();
 
-        assert_eq!(format!("{:?}", db.0), "Database");
+        let uri = Arc::new(Url::from_file_path("/t/tests/a.zeek").unwrap());
+        let lib = Arc::new(Url::from_file_path("/t/tests/a.zeek/lib.zeek").unwrap());
+
+        db.add_file(
+            (*uri).clone(),
+            "# @TEST-EXEC: zeek -b %INPUT\n@load ./lib\nprint x;\n@TEST-START-FILE lib.zeek\nglobal x = 1;\n@TEST-END-FILE\n",
+        );
+
+        assert_eq!(
+            db.0.loaded_files(Arc::clone(&uri)).as_ref(),
+            [Arc::clone(&lib)]
+        );
+
+        let x =
+            db.0.resolve_id(
+                "x".into(),
+                NodeLocation::from_range(
+                    Arc::clone(&uri),
+                    Range::new(Position::new(2, 6), Position::new(2, 7)),
+                ),
+            )
+            .unwrap();
+        assert_eq!(x.loc.as_ref().map(|l| &l.uri), Some(&lib));
     }
 
     #[tokio::test]
@@ -2226,8 +3637,10 @@ local x = f();
         let prefix = Path::new("/prefix");
         db.add_prefix(prefix.to_str().unwrap());
         db.add_file(
-            Url::from_file_path(prefix.join(zeek::essential_input_files().first().unwrap()))
-                .unwrap(),
+            Url::from_file_path(
+                prefix.join(zeek::essential_input_files(None, false).first().unwrap()),
+            )
+            .unwrap(),
             "
             ##Declaration.
             global zeek_init: event();",
@@ -2547,6 +3960,90 @@ event zeek_init() {}",
         );
     }
 
+    #[tokio::test]
+    async fn sig() {
+        let mut db = TestDatabase::default();
+
+        let uri_zeek = Url::from_file_path("/x/x.zeek").unwrap();
+        db.add_file(
+            uri_zeek.clone(),
+            "module x;
+@load-sigs ./x
+export { global check: function(state: signature_state, data: string): bool; }",
+        );
+
+        let uri = Url::from_file_path("/x/x.sig").unwrap();
+        db.add_file(
+            uri.clone(),
+            "signature a {
+    eval x::check
+}
+signature b {
+    requires-signature a
+    unknown
+}",
+        );
+
+        assert_eq!(
+            super::sig_loaders(&db.0, &uri),
+            [Arc::new(uri_zeek.clone())]
+        );
+
+        let server = serve(db);
+
+        let goto = |line, character| {
+            server.goto_definition(super::GotoDefinitionParams {
+                text_document_position_params: TextDocumentPositionParams::new(
+                    TextDocumentIdentifier::new(uri.clone()),
+                    Position::new(line, character),
+                ),
+                partial_result_params: PartialResultParams::default(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+        };
+
+        let Ok(Some(GotoDefinitionResponse::Scalar(check))) = goto(1, 10).await else {
+            panic!()
+        };
+        assert_eq!(check.uri, uri_zeek);
+        assert_eq!(check.range.start.line, 2);
+
+        let Ok(Some(GotoDefinitionResponse::Scalar(a))) = goto(4, 24).await else {
+            panic!()
+        };
+        assert_eq!(
+            a,
+            Location::new(
+                uri.clone(),
+                Range::new(Position::new(0, 10), Position::new(0, 11))
+            )
+        );
+
+        let Ok(Some(DocumentSymbolResponse::Nested(symbols))) = server
+            .document_symbol(DocumentSymbolParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await
+        else {
+            panic!()
+        };
+        assert_eq!(
+            symbols.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+
+        let file = server.state.read().await.parse_sig(Arc::new(uri)).unwrap();
+        assert_eq!(
+            super::sig_diagnostics(&file)
+                .iter()
+                .map(|d| d.message.as_str())
+                .collect::<Vec<_>>(),
+            ["unknown signature attribute 'unknown'"]
+        );
+    }
+
     #[tokio::test]
     async fn goto_definition_zeekygen() {
         let mut db = TestDatabase::default();
@@ -2768,7 +4265,8 @@ event x::foo() {}",
 
     #[tokio::test]
     async fn code_action() {
-        use super::{tree_diagnostics, CodeActionParams};
+        use super::CodeActionParams;
+        use crate::syntax;
         use tower_lsp::lsp_types::CodeActionContext;
 
         let mut db = TestDatabase::default();
@@ -2780,7 +4278,7 @@ event x::foo() {}",
         let context =
             db.0.parse(uri.clone())
                 .map(|t| CodeActionContext {
-                    diagnostics: tree_diagnostics(&t.root_node()),
+                    diagnostics: syntax::diagnostics(t.root_node(), source),
                     ..CodeActionContext::default()
                 })
                 .unwrap();
@@ -2791,7 +4289,7 @@ event x::foo() {}",
             server
                 .code_action(CodeActionParams {
                     text_document: TextDocumentIdentifier::new((*uri).clone()),
-                    range: Range::new(Position::new(0, 1), Position::new(0, 2)),
+                    range: Range::new(Position::new(0, 0), Position::new(0, 13)),
                     context,
                     work_done_progress_params: WorkDoneProgressParams::default(),
                     partial_result_params: PartialResultParams::default(),
@@ -2800,6 +4298,374 @@ event x::foo() {}",
         );
     }
 
+    #[tokio::test]
+    async fn generate_handler() {
+        use super::{CodeActionParams, COMMAND_GENERATE_HANDLER};
+        use tower_lsp::lsp_types::{
+            CodeActionContext, CodeActionOrCommand, ExecuteCommandParams, WorkspaceEdit,
+        };
+
+        let mut db = TestDatabase::default();
+        db.add_prefix("/p");
+        db.add_file(
+            Url::from_file_path("/p/a.zeek").unwrap(),
+            "module A;\nexport {\n    global e: event(c: count, s: string);\n}\n",
+        );
+
+        let uri = Url::from_file_path("/x.zeek").unwrap();
+        db.add_file(uri.clone(), "@load foo\nevent zeek_init() { A::e; }");
+
+        let server = serve(db);
+
+        let actions = server
+            .code_action(CodeActionParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                range: Range::new(Position::new(1, 21), Position::new(1, 21)),
+                context: CodeActionContext::default(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        let edits = actions
+            .into_iter()
+            .map(|a| {
+                let CodeActionOrCommand::CodeAction(a) = a else {
+                    panic!("expected code action")
+                };
+                let mut changes = a.edit.unwrap().changes.unwrap();
+                let edits = changes.remove(&uri).unwrap();
+                (
+                    a.title,
+                    edits.into_iter().map(|e| e.new_text).collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            edits,
+            vec![
+                (
+                    "Generate handler for event 'A::e'".to_string(),
+                    vec![
+                        "@load a\n".to_string(),
+                        "\n\nevent A::e(c: count, s: string)\n\t{\n\t}\n".to_string()
+                    ]
+                ),
+                (
+                    "Generate handler for event 'A::e' with &priority".to_string(),
+                    vec![
+                        "@load a\n".to_string(),
+                        "\n\nevent A::e(c: count, s: string) &priority=0\n\t{\n\t}\n".to_string()
+                    ]
+                ),
+            ]
+        );
+
+        let edit: WorkspaceEdit = serde_json::from_value(
+            server
+                .execute_command(ExecuteCommandParams {
+                    command: COMMAND_GENERATE_HANDLER.into(),
+                    arguments: vec![json!(uri), json!("A::e"), json!(5)],
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                })
+                .await
+                .unwrap()
+                .unwrap(),
+        )
+        .unwrap();
+        let edits = edit.changes.unwrap().remove(&uri).unwrap();
+        assert_eq!(edits[0].range.start, Position::new(1, 0));
+        assert_eq!(
+            edits[1].new_text,
+            "\n\nevent A::e(c: count, s: string) &priority=5\n\t{\n\t}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn enum_members() {
+        let mut db = TestDatabase::default();
+        let notice = Url::from_file_path("/notice.zeek").unwrap();
+        db.add_file(
+            notice.clone(),
+            "module Notice; export { type Type: enum { Tally }; }",
+        );
+        let ssh = Url::from_file_path("/ssh.zeek").unwrap();
+        db.add_file(
+            ssh.clone(),
+            "module SSH;\nredef enum Notice::Type += {\n\t## Guessing.\n\tPassword_Guessing,\n};",
+        );
+
+        let server = serve(db);
+
+        let members = server
+            .enum_members(super::EnumMembersParams {
+                id: "Notice::Type".into(),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            members
+                .iter()
+                .map(|m| (m.id.as_str(), m.location.as_ref().map(|l| &l.uri)))
+                .collect::<Vec<_>>(),
+            [
+                ("Notice::Tally", Some(&notice)),
+                ("SSH::Password_Guessing", Some(&ssh)),
+            ]
+        );
+        assert!(members[1].documentation.contains("Guessing."));
+    }
+
+    #[tokio::test]
+    async fn count_lenses() {
+        use tower_lsp::lsp_types::{CodeLensParams, Location};
+
+        let mut db = TestDatabase::default();
+        let uri = Url::from_file_path("/x.zeek").unwrap();
+        db.add_file(
+            uri.clone(),
+            "global e: event(c: count);
+type R: record { a: count; };
+global x = 1;",
+        );
+        db.add_file(
+            Url::from_file_path("/y.zeek").unwrap(),
+            "@load ./x
+event e(c: count) {}
+event e(c: count) {}
+redef record R += { b: count &optional; };",
+        );
+
+        let server = serve(db);
+
+        let lenses = server
+            .code_lens(CodeLensParams {
+                text_document: TextDocumentIdentifier::new(uri),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Lenses are resolved lazily.
+        assert_eq!(lenses.len(), 2);
+        assert!(lenses.iter().all(|l| l.command.is_none()));
+
+        let mut titles = Vec::new();
+        for lens in lenses {
+            let command = server
+                .code_lens_resolve(lens)
+                .await
+                .unwrap()
+                .command
+                .unwrap();
+            assert_eq!(command.command, super::COMMAND_SHOW_LOCATIONS);
+
+            let locations: Vec<Location> =
+                serde_json::from_value(command.arguments.unwrap()[2].clone()).unwrap();
+            titles.push((command.title, locations.len()));
+        }
+        titles.sort();
+
+        assert_eq!(
+            titles,
+            [("1 redef".to_string(), 1), ("2 handlers".to_string(), 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn type_hierarchy() {
+        use tower_lsp::lsp_types::{
+            HoverContents, MarkedString, TypeHierarchyPrepareParams, TypeHierarchySubtypesParams,
+            TypeHierarchySupertypesParams,
+        };
+
+        let mut db = TestDatabase::default();
+        let x = Url::from_file_path("/x.zeek").unwrap();
+        db.add_file(x.clone(), "type R: record { a: count; };");
+        let y = Url::from_file_path("/y.zeek").unwrap();
+        db.add_file(
+            y.clone(),
+            "@load ./x
+redef record R += { b: string &optional; };
+global r: R;",
+        );
+
+        let server = serve(db);
+
+        let prepare = |uri: &Url, position| TypeHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(uri.clone()),
+                position,
+            ),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+
+        let base = server
+            .prepare_type_hierarchy(prepare(&y, Position::new(2, 10)))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(base.len(), 1);
+        assert_eq!(base[0].name, "R");
+        assert_eq!(base[0].uri, x);
+
+        let redefs = server
+            .subtypes(TypeHierarchySubtypesParams {
+                item: base[0].clone(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(redefs.len(), 1);
+        assert_eq!(redefs[0].uri, y);
+        assert_eq!(redefs[0].detail.as_deref(), Some("redef record"));
+
+        let redef = server
+            .prepare_type_hierarchy(prepare(&y, Position::new(1, 13)))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(redef, redefs);
+
+        let supertypes = server
+            .supertypes(TypeHierarchySupertypesParams {
+                item: redef[0].clone(),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(supertypes, base);
+
+        let hover = server
+            .hover(HoverParams {
+                text_document_position_params: TextDocumentPositionParams::new(
+                    TextDocumentIdentifier::new(y),
+                    Position::new(2, 10),
+                ),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let HoverContents::Array(contents) = hover.contents else {
+            panic!()
+        };
+        assert_eq!(
+            contents.last(),
+            Some(&MarkedString::String(
+                "Fields from `/x.zeek`:\n- `a: count`\n\nFields from `/y.zeek`:\n- `b: string &optional`"
+                    .into()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn zeek_version() {
+        use tower_lsp::lsp_types::{HoverContents, MarkedString};
+
+        let mut db = TestDatabase::default();
+        let uri = Url::from_file_path("/x.zeek").unwrap();
+        db.add_file(
+            uri.clone(),
+            "@if ( Version::at_least(\"6.0\") )\nglobal x = 1;\n@endif",
+        );
+        db.0.set_zeek_version(Some(Version::new(6, 2, 1)));
+
+        let server = serve(db);
+
+        assert_eq!(
+            server.status().await.unwrap(),
+            json!({"zeek_version": "6.2.1", "prefixes": [], "files": 1})
+        );
+
+        let hover = server
+            .hover(HoverParams {
+                text_document_position_params: TextDocumentPositionParams::new(
+                    TextDocumentIdentifier::new(uri),
+                    Position::new(0, 10),
+                ),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let HoverContents::Array(contents) = hover.contents else {
+            panic!()
+        };
+        assert_eq!(
+            contents.first(),
+            Some(&MarkedString::String(
+                "Evaluates to `T` with Zeek `6.2.1`".into()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn deprecation() {
+        use tower_lsp::lsp_types::{DiagnosticTag, SymbolTag};
+
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            r#"global x = 1 &deprecated="Use y instead.";
+global y = 2;
+function f() &deprecated {}
+event zeek_init() { print x, y; f(); }"#,
+        );
+
+        let diags = lint::diagnostics(&db.0, Arc::clone(&uri))
+            .into_iter()
+            .filter(|d| lint::Rule::Deprecated.matches(d))
+            .map(|d| (d.range.start, d.message, d.tags))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diags,
+            vec![
+                (
+                    Position::new(3, 26),
+                    "'x' is deprecated: Use y instead.".to_string(),
+                    Some(vec![DiagnosticTag::DEPRECATED])
+                ),
+                (
+                    Position::new(3, 32),
+                    "'f' is deprecated".to_string(),
+                    Some(vec![DiagnosticTag::DEPRECATED])
+                ),
+            ]
+        );
+
+        let server = serve(db);
+        let Some(DocumentSymbolResponse::Nested(symbols)) = server
+            .document_symbol(DocumentSymbolParams {
+                text_document: TextDocumentIdentifier::new((*uri).clone()),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await
+            .unwrap()
+        else {
+            panic!()
+        };
+        let mut deprecated = symbols
+            .into_iter()
+            .filter(|s| s.tags == Some(vec![SymbolTag::DEPRECATED]))
+            .map(|s| s.name)
+            .collect::<Vec<_>>();
+        deprecated.sort();
+        assert_eq!(deprecated, vec!["f", "x"]);
+    }
+
     #[tokio::test]
     async fn inlay_hint_function_params() {
         let mut db = TestDatabase::default();
@@ -2868,17 +4734,17 @@ option x = T;
             lsp::InitializationOptions {
                 inlay_hints_variables: false,
                 inlay_hints_parameters: false,
-                ..default
+                ..default.clone()
             },
             lsp::InitializationOptions {
                 inlay_hints_variables: true,
                 inlay_hints_parameters: false,
-                ..default
+                ..default.clone()
             },
             lsp::InitializationOptions {
                 inlay_hints_variables: false,
                 inlay_hints_parameters: true,
-                ..default
+                ..default.clone()
             },
             lsp::InitializationOptions {
                 inlay_hints_variables: true,
@@ -2915,6 +4781,7 @@ const x = 1;
     }
 
     #[test]
+    #[allow(clippy::too_many_lines)]
     fn deserialize_initialization_options() {
         use lsp::InitializationOptions;
         use serde_json::json;
@@ -2930,6 +4797,11 @@ const x = 1;
                 semantic_highlighting: true,
                 debug_ast_nodes: false,
                 enable_filewatcher: false,
+                zeek_version: None,
+                defines: Vec::new(),
+                bare_mode: false,
+                unused_handler_parameters: false,
+                lints: std::collections::BTreeMap::new(),
             }
         );
 
@@ -3002,6 +4874,33 @@ const x = 1;
                 ..InitializationOptions::new()
             }
         );
+
+        assert_eq!(
+            serde_json::from_value::<InitializationOptions>(
+                json!({"unused_handler_parameters": true})
+            )
+            .unwrap(),
+            InitializationOptions {
+                unused_handler_parameters: true,
+                ..InitializationOptions::new()
+            }
+        );
+
+        assert_eq!(
+            serde_json::from_value::<InitializationOptions>(
+                json!({"lints": {"unused-local": "off", "shadowed-declaration": "error"}})
+            )
+            .unwrap(),
+            InitializationOptions {
+                lints: [
+                    ("unused-local".into(), lint::Level::Off),
+                    ("shadowed-declaration".into(), lint::Level::Error),
+                ]
+                .into_iter()
+                .collect(),
+                ..InitializationOptions::new()
+            }
+        );
     }
 
     #[tokio::test]
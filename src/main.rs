@@ -1,10 +1,11 @@
 use {
-    clap::Parser,
+    clap::{Parser, Subcommand},
     eyre::Result,
+    std::path::PathBuf,
     tracing::info,
     tracing_appender::non_blocking::WorkerGuard,
     tracing_subscriber::{layer::SubscriberExt, prelude::*, util::SubscriberInitExt},
-    zeek_language_server::lsp::run,
+    zeek_language_server::{lint, logs, lsp::run},
 };
 
 #[cfg(feature = "telemetry")]
@@ -28,6 +29,32 @@ struct Args {
     /// Valid levels are: trace, debug, info, warn, error.
     #[clap(short, long, value_enum, default_value = "error")]
     filter: tracing::Level,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the columns of all logs created by the given scripts.
+    LogSchema {
+        /// Output format.
+        #[clap(long, value_enum, default_value = "json")]
+        format: logs::Format,
+
+        /// Scripts, or directories containing scripts.
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Check the given scripts for common mistakes.
+    ///
+    /// Exits with a non-zero status if any issues were found.
+    Lint {
+        /// Scripts, or directories containing scripts.
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
 }
 
 #[allow(clippy::unnecessary_wraps)]
@@ -73,6 +100,23 @@ async fn main() -> Result<()> {
 
     let _guard = init_logging(&args)?;
 
+    match &args.command {
+        Some(Command::LogSchema { format, paths }) => {
+            println!("{}", logs::extract(paths, *format).await?);
+            return Ok(());
+        }
+        Some(Command::Lint { paths }) => {
+            let diagnostics = lint::check(paths).await;
+            if diagnostics.is_empty() {
+                return Ok(());
+            }
+
+            println!("{}", lint::render(&diagnostics));
+            std::process::exit(1);
+        }
+        None => {}
+    }
+
     info!("starting Zeek language server");
 
     run().await;
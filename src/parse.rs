@@ -1,4 +1,4 @@
-use crate::{query::Node, Files};
+use crate::{query::Node, sig, Files};
 use std::sync::Arc;
 use tower_lsp::lsp_types::Url;
 use tracing::instrument;
@@ -33,10 +33,20 @@ impl Eq for Tree {}
 pub trait Parse: Files {
     #[must_use]
     fn parse(&self, file: Arc<Url>) -> Option<Arc<Tree>>;
+
+    /// Parse a signature file.
+    #[must_use]
+    #[salsa::invoke(sig::parse_sig)]
+    fn parse_sig(&self, file: Arc<Url>) -> Option<Arc<sig::File>>;
 }
 
 #[instrument(skip(db))]
 fn parse(db: &dyn Parse, file: Arc<Url>) -> Option<Arc<Tree>> {
+    // Signature files are not Zeek script.
+    if sig::is_sig_file(&file) {
+        return None;
+    }
+
     let mut parser = Parser::new();
     parser
         .set_language(&language_zeek())
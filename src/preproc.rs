@@ -0,0 +1,471 @@
+use std::sync::LazyLock;
+
+use semver::Version;
+use tower_lsp::lsp_types::{Position, Range};
+
+/// A conditional preprocessor directive opening a block.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Condition<'a> {
+    /// `@if ( expr )`
+    If(&'a str),
+
+    /// `@ifdef ( id )`
+    Ifdef(&'a str),
+
+    /// `@ifndef ( id )`
+    Ifndef(&'a str),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Directive<'a> {
+    Open(Condition<'a>),
+    Else,
+    Endif,
+}
+
+fn directive(line: &str) -> Option<Directive<'_>> {
+    static RE: LazyLock<regex::Regex> = LazyLock::new(|| {
+        regex::Regex::new(r"^\s*@(ifdef|ifndef|if|else|endif)\b\s*(.*)$").expect("invalid regexp")
+    });
+
+    let captures = RE.captures(line)?;
+
+    // Drop trailing comments.
+    let cond = || {
+        let cond = captures.get(2).map_or("", |c| c.as_str());
+        cond.split_once('#').map_or(cond, |(c, _)| c).trim()
+    };
+
+    // Drop the parens around an identifier.
+    let id = || {
+        let id = cond();
+        id.strip_prefix('(')
+            .and_then(|c| c.strip_suffix(')'))
+            .unwrap_or(id)
+            .trim()
+    };
+
+    Some(match captures.get(1)?.as_str() {
+        "if" => Directive::Open(Condition::If(cond())),
+        "ifdef" => Directive::Open(Condition::Ifdef(id())),
+        "ifndef" => Directive::Open(Condition::Ifndef(id())),
+        "else" => Directive::Else,
+        "endif" => Directive::Endif,
+        _ => return None,
+    })
+}
+
+/// Get the condition of a line holding a conditional directive like `@if ( T )`.
+#[must_use]
+pub fn condition(line: &str) -> Option<Condition<'_>> {
+    match directive(line)? {
+        Directive::Open(cond) => Some(cond),
+        Directive::Else | Directive::Endif => None,
+    }
+}
+
+/// Compute the ranges of inactive branches of conditional directives in `source`.
+///
+/// Conditions are evaluated with `eval`. If a condition cannot be evaluated, i.e., `eval` returns
+/// `None`, all its branches are considered active. Ranges of nested inactive blocks are contained
+/// in the range of their inactive parent and are not reported separately.
+#[must_use]
+pub fn inactive_ranges(source: &str, eval: impl Fn(Condition) -> Option<bool>) -> Vec<Range> {
+    struct Block {
+        cond: Option<bool>,
+        in_else: bool,
+        start: u32,
+        in_inactive: bool,
+    }
+
+    impl Block {
+        fn is_inactive(&self) -> bool {
+            self.cond.is_some_and(|c| c == self.in_else)
+        }
+
+        fn close(&self, end: u32, ranges: &mut Vec<Range>) {
+            if !self.in_inactive && self.is_inactive() && self.start < end {
+                ranges.push(Range::new(
+                    Position::new(self.start, 0),
+                    Position::new(end, 0),
+                ));
+            }
+        }
+    }
+
+    let mut ranges = Vec::new();
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut num_lines = 0;
+
+    for (line, text) in (0..).zip(source.lines()) {
+        num_lines = line + 1;
+
+        match directive(text) {
+            Some(Directive::Open(cond)) => {
+                let in_inactive = blocks
+                    .last()
+                    .is_some_and(|b| b.in_inactive || b.is_inactive());
+
+                blocks.push(Block {
+                    // No need to evaluate conditions in blocks which are already inactive.
+                    cond: if in_inactive { None } else { eval(cond) },
+                    in_else: false,
+                    start: line + 1,
+                    in_inactive,
+                });
+            }
+            Some(Directive::Else) => {
+                if let Some(b) = blocks.last_mut() {
+                    b.close(line, &mut ranges);
+                    b.in_else = true;
+                    b.start = line + 1;
+                }
+            }
+            Some(Directive::Endif) => {
+                if let Some(b) = blocks.pop() {
+                    b.close(line, &mut ranges);
+                }
+            }
+            None => {}
+        }
+    }
+
+    // Close any unterminated blocks at the end of the file.
+    while let Some(b) = blocks.pop() {
+        b.close(num_lines, &mut ranges);
+    }
+
+    ranges.sort_by_key(|r| r.start);
+    ranges
+}
+
+/// Parse a Zeek version like `6.0`, `6.2.1` or `7.0.0-dev.123` leniently.
+#[must_use]
+pub fn parse_version(version: &str) -> Option<Version> {
+    let mut parts = version
+        .trim()
+        .split(['.', '-'])
+        .map_while(|p| p.parse::<u64>().ok());
+
+    let major = parts.next()?;
+    let minor = parts.next().unwrap_or(0);
+    let patch = parts.next().unwrap_or(0);
+
+    Some(Version::new(major, minor, patch))
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token<'a> {
+    Open,
+    Close,
+    Comma,
+    Not,
+    And,
+    Or,
+    Cmp(&'a str),
+    Id(&'a str),
+    Int(i64),
+    Str(&'a str),
+}
+
+fn tokenize(expr: &str) -> Option<Vec<Token<'_>>> {
+    static RE: LazyLock<regex::Regex> = LazyLock::new(|| {
+        regex::Regex::new(
+            r#"^\s*(?:(\()|(\))|(,)|(&&)|(\|\|)|(==|!=|<=|>=|<|>)|(!)|([A-Za-z_][A-Za-z0-9_]*(?:::[A-Za-z_][A-Za-z0-9_]*)*)|([0-9]+)|"([^"]*)")"#,
+        )
+        .expect("invalid regexp")
+    });
+
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_end();
+
+    while !rest.is_empty() {
+        let captures = RE.captures(rest)?;
+        let token = if captures.get(1).is_some() {
+            Token::Open
+        } else if captures.get(2).is_some() {
+            Token::Close
+        } else if captures.get(3).is_some() {
+            Token::Comma
+        } else if captures.get(4).is_some() {
+            Token::And
+        } else if captures.get(5).is_some() {
+            Token::Or
+        } else if let Some(c) = captures.get(6) {
+            Token::Cmp(c.as_str())
+        } else if captures.get(7).is_some() {
+            Token::Not
+        } else if let Some(c) = captures.get(8) {
+            Token::Id(c.as_str())
+        } else if let Some(c) = captures.get(9) {
+            Token::Int(c.as_str().parse().ok()?)
+        } else {
+            Token::Str(captures.get(10)?.as_str())
+        };
+
+        tokens.push(token);
+        rest = &rest[captures.get(0)?.end()..];
+    }
+
+    Some(tokens)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Value {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+}
+
+/// Recursive descent evaluator for `@if` conditions.
+///
+/// Values which cannot be determined are represented as `None` and propagated with three-valued
+/// logic, e.g., `F && <unknown>` is `F`, but `T && <unknown>` is unknown.
+struct Evaluator<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    version: Option<&'a Version>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), ()> {
+        if self.next().as_ref() == Some(token) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    fn or(&mut self) -> Result<Option<Value>, ()> {
+        let mut lhs = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            let rhs = self.and()?;
+            lhs = match (as_bool(lhs.as_ref()), as_bool(rhs.as_ref())) {
+                (Some(true), _) | (_, Some(true)) => Some(Value::Bool(true)),
+                (Some(false), Some(false)) => Some(Value::Bool(false)),
+                _ => None,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Option<Value>, ()> {
+        let mut lhs = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            let rhs = self.not()?;
+            lhs = match (as_bool(lhs.as_ref()), as_bool(rhs.as_ref())) {
+                (Some(false), _) | (_, Some(false)) => Some(Value::Bool(false)),
+                (Some(true), Some(true)) => Some(Value::Bool(true)),
+                _ => None,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn not(&mut self) -> Result<Option<Value>, ()> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(as_bool(self.not()?.as_ref()).map(|b| Value::Bool(!b)));
+        }
+
+        self.cmp()
+    }
+
+    fn cmp(&mut self) -> Result<Option<Value>, ()> {
+        let lhs = self.primary()?;
+
+        let Some(Token::Cmp(op)) = self.peek().cloned() else {
+            return Ok(lhs);
+        };
+        self.next();
+
+        let rhs = self.primary()?;
+
+        let ordering = match (lhs, rhs) {
+            (Some(Value::Int(a)), Some(Value::Int(b))) => a.cmp(&b),
+            (Some(Value::Str(a)), Some(Value::Str(b))) => a.cmp(&b),
+            (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(&b),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Value::Bool(match op {
+            "==" => ordering.is_eq(),
+            "!=" => ordering.is_ne(),
+            "<" => ordering.is_lt(),
+            "<=" => ordering.is_le(),
+            ">" => ordering.is_gt(),
+            ">=" => ordering.is_ge(),
+            _ => return Err(()),
+        })))
+    }
+
+    fn primary(&mut self) -> Result<Option<Value>, ()> {
+        match self.next().ok_or(())? {
+            Token::Open => {
+                let value = self.or()?;
+                self.expect(&Token::Close)?;
+                Ok(value)
+            }
+            Token::Int(i) => Ok(Some(Value::Int(i))),
+            Token::Str(s) => Ok(Some(Value::Str(s.into()))),
+            Token::Id("T") => Ok(Some(Value::Bool(true))),
+            Token::Id("F") => Ok(Some(Value::Bool(false))),
+            Token::Id(id) if self.peek() == Some(&Token::Open) => {
+                self.next();
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::Close) {
+                    loop {
+                        args.push(self.or()?);
+                        if self.peek() == Some(&Token::Comma) {
+                            self.next();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+                self.expect(&Token::Close)?;
+
+                Ok(self.call(id, &args))
+            }
+            Token::Id("Version::number") => Ok(self.version.and_then(|v| {
+                let number = v.major * 10_000 + v.minor * 100 + v.patch;
+                Some(Value::Int(i64::try_from(number).ok()?))
+            })),
+            Token::Id(_) => Ok(None),
+            _ => Err(()),
+        }
+    }
+
+    fn call(&self, id: &str, args: &[Option<Value>]) -> Option<Value> {
+        match (id, args) {
+            ("Version::at_least", [Some(Value::Str(v))]) => {
+                let version = self.version?;
+                let required = parse_version(v)?;
+                Some(Value::Bool(*version >= required))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn as_bool(value: Option<&Value>) -> Option<bool> {
+    match value {
+        Some(Value::Bool(b)) => Some(*b),
+        _ => None,
+    }
+}
+
+/// Evaluate the expression of an `@if` directive.
+///
+/// We support boolean literals, `!`, `&&` and `||`, comparisons, `Version::number` and
+/// `Version::at_least`. Returns `None` if the expression cannot be evaluated, e.g., since it
+/// references other identifiers or no Zeek version is known.
+#[must_use]
+pub fn eval_if(expr: &str, version: Option<&Version>) -> Option<bool> {
+    let mut evaluator = Evaluator {
+        tokens: tokenize(expr)?,
+        pos: 0,
+        version,
+    };
+
+    let value = evaluator.or().ok()?;
+
+    // Reject trailing garbage.
+    if evaluator.pos != evaluator.tokens.len() {
+        return None;
+    }
+
+    as_bool(value.as_ref())
+}
+
+#[cfg(test)]
+mod test {
+    use semver::Version;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::{eval_if, inactive_ranges, parse_version, Condition};
+
+    #[test]
+    fn version() {
+        assert_eq!(parse_version("6.0"), Some(Version::new(6, 0, 0)));
+        assert_eq!(parse_version("6.2.1"), Some(Version::new(6, 2, 1)));
+        assert_eq!(parse_version("7.0.0-dev.123"), Some(Version::new(7, 0, 0)));
+        assert_eq!(parse_version("7"), Some(Version::new(7, 0, 0)));
+        assert_eq!(parse_version("abc"), None);
+    }
+
+    #[test]
+    fn eval() {
+        let v = Version::new(6, 2, 0);
+        let v = Some(&v);
+
+        assert_eq!(eval_if("T", v), Some(true));
+        assert_eq!(eval_if("!T", v), Some(false));
+        assert_eq!(eval_if("Version::at_least(\"6.0\")", v), Some(true));
+        assert_eq!(eval_if("Version::at_least(\"7.0\")", v), Some(false));
+        assert_eq!(eval_if("! Version::at_least(\"7.0\")", v), Some(true));
+        assert_eq!(eval_if("Version::number >= 60200", v), Some(true));
+        assert_eq!(eval_if("Version::number < 60000", v), Some(false));
+        assert_eq!(
+            eval_if("Version::at_least(\"6.0\") && Version::number < 70000", v),
+            Some(true)
+        );
+
+        // Unknown values.
+        assert_eq!(eval_if("Version::at_least(\"6.0\")", None), None);
+        assert_eq!(eval_if("Cluster::is_enabled()", v), None);
+        assert_eq!(eval_if("T && Cluster::is_enabled()", v), None);
+        assert_eq!(eval_if("F && Cluster::is_enabled()", v), Some(false));
+        assert_eq!(eval_if("T || Cluster::is_enabled()", v), Some(true));
+
+        // Malformed input.
+        assert_eq!(eval_if("(T", v), None);
+        assert_eq!(eval_if("T T", v), None);
+    }
+
+    #[test]
+    fn ranges() {
+        let source = r#"@if ( Version::at_least("6.0") )
+global a = 1;
+@else
+global a = 2;
+@endif
+@ifdef ( X )
+global b = 1;
+@ifdef ( Y )
+global c = 1;
+@endif
+@endif
+@ifndef ( X )
+global d = 1;
+@endif
+@if ( Foo::bar() )
+global e = 1;
+@else
+global e = 2;
+@endif
+"#;
+
+        let v = Version::new(6, 0, 0);
+        let ranges = inactive_ranges(source, |cond| match cond {
+            Condition::If(expr) => eval_if(expr, Some(&v)),
+            Condition::Ifdef(id) => (id == "X").then_some(true),
+            Condition::Ifndef(id) => (id == "X").then_some(false),
+        });
+
+        let range = |start, end| Range::new(Position::new(start, 0), Position::new(end, 0));
+        assert_eq!(ranges, vec![range(3, 4), range(12, 13)]);
+    }
+}
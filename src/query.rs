@@ -120,6 +120,23 @@ pub struct Decl {
     pub is_export: Option<bool>,
     pub loc: Option<Location>,
     pub documentation: Str,
+    pub attrs: Vec<Attr>,
+}
+
+impl Decl {
+    /// Get the deprecation message if the decl is marked `&deprecated`.
+    ///
+    /// If no message was given this returns an empty message.
+    #[must_use]
+    pub fn deprecation(&self) -> Option<Str> {
+        let attr = self.attrs.iter().find(|a| a.name == "deprecated")?;
+        Some(
+            attr.value
+                .as_deref()
+                .map_or("", |v| v.trim_matches('"'))
+                .into(),
+        )
+    }
 }
 
 impl PartialOrd for Decl {
@@ -150,7 +167,11 @@ impl Ord for Decl {
             core::cmp::Ordering::Equal => {}
             ord => return ord,
         }
-        self.documentation.cmp(&other.documentation)
+        match self.documentation.cmp(&other.documentation) {
+            core::cmp::Ordering::Equal => {}
+            ord => return ord,
+        }
+        self.attrs.cmp(&other.attrs)
     }
 }
 
@@ -164,9 +185,94 @@ impl Hash for Decl {
         self.is_export.hash(state);
         self.loc.hash(state);
         self.documentation.hash(state);
+        self.attrs.hash(state);
     }
 }
 
+/// An attribute like `&optional` or `&default=0` attached to a declaration.
+#[derive(Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
+pub struct Attr {
+    /// Name of the attribute without the leading `&`, e.g., `optional`.
+    pub name: Str,
+
+    /// Source of the attribute value if any, e.g., `0` for `&default=0`.
+    pub value: Option<Str>,
+}
+
+impl Attr {
+    fn from_node(node: Node, source: &[u8]) -> Option<Self> {
+        let children = node.children();
+        let name = children
+            .first()?
+            .utf8_text(source)
+            .ok()?
+            .strip_prefix('&')?;
+        let value = children
+            .get(2)
+            .and_then(|v| v.utf8_text(source).ok())
+            .map(Str::from);
+
+        Some(Self {
+            name: name.into(),
+            value,
+        })
+    }
+
+    /// Get the documentation for this attribute.
+    #[must_use]
+    pub fn documentation(&self) -> Option<&'static str> {
+        attr_documentation(&self.name)
+    }
+}
+
+/// Known attributes with their documentation.
+pub const ATTRIBUTES: &[(&str, &str)] = &[
+    ("add_func", "Function to call for each value added to a container via `+=` redefs."),
+    ("backend", "Store a table or set in a Broker backend, e.g., `&backend=Broker::MEMORY`."),
+    ("broker_allow_complex_type", "Allow storing complex types with `&backend`."),
+    ("broker_store", "Store a table or set in the given Broker store."),
+    ("create_expire", "Expire container elements a given interval after they were created."),
+    ("default", "Default value for record fields, function parameters, or table lookups of missing indices."),
+    ("default_insert", "Like `&default`, but also inserts the default value into the table."),
+    ("delete_func", "Function to call for each value removed from a container via `-=` redefs."),
+    ("deprecated", "Marks an identifier as deprecated, optionally with a message."),
+    ("error_handler", "Marks an event handler as handling errors; suppresses recursive error reporting."),
+    ("expire_func", "Function called when a container element expires; it returns an additional `interval` to keep the element."),
+    ("is_assigned", "Suppress warnings about an identifier being used without being assigned."),
+    ("is_used", "Suppress warnings about an identifier not being used."),
+    ("log", "Include a record field, or all fields of a record, in logs."),
+    ("on_change", "Function called when a table or set is modified."),
+    ("optional", "Record field which does not need to be set."),
+    ("ordered", "Iterate a table or set in insertion order."),
+    ("priority", "Priority of an event or hook handler; handlers with higher priority run first."),
+    ("raw_output", "Do not escape non-printable characters when writing to a file."),
+    ("read_expire", "Expire container elements a given interval after they were last read."),
+    ("redef", "Allow redefining a constant or option with `redef`."),
+    ("type_column", "Name of the column holding the type for `any` fields when reading input."),
+    ("write_expire", "Expire container elements a given interval after they were last written."),
+];
+
+/// Get the documentation for an attribute with the given name (without leading `&`).
+#[must_use]
+pub fn attr_documentation(name: &str) -> Option<&'static str> {
+    ATTRIBUTES
+        .iter()
+        .find_map(|(n, docs)| if *n == name { Some(*docs) } else { None })
+}
+
+/// Extract the attributes attached to a node.
+#[must_use]
+pub fn attrs(node: Node, source: &[u8]) -> Vec<Attr> {
+    node.named_child("attr_list")
+        .map_or_else(
+            || node.named_children("attr"),
+            |xs| xs.named_children("attr"),
+        )
+        .into_iter()
+        .filter_map(|a| Attr::from_node(a, source))
+        .collect()
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct FunctionCall {
     pub f: NodeLocation,
@@ -350,6 +456,22 @@ impl<'a> Node<'a> {
             .collect()
     }
 
+    /// Get all children of the node, including anonymous ones, but without `nl` nodes.
+    #[must_use]
+    pub fn children(&self) -> Vec<Self> {
+        let mut cur = self.0.walk();
+        self.0
+            .children(&mut cur)
+            .filter(|n| n.kind() != "nl")
+            .map(Into::into)
+            .collect()
+    }
+
+    #[must_use]
+    pub fn is_named(&self) -> bool {
+        self.0.is_named()
+    }
+
     #[must_use]
     fn next_sibling(&self) -> Option<Self> {
         let mut n = self.0;
@@ -364,7 +486,7 @@ impl<'a> Node<'a> {
     }
 
     #[must_use]
-    fn prev_sibling(&self) -> Option<Self> {
+    pub fn prev_sibling(&self) -> Option<Self> {
         let mut n = self.0;
         while let Some(p) = n.prev_named_sibling() {
             if p.kind() != "nl" {
@@ -587,6 +709,7 @@ pub fn decls_(node: Node, uri: Arc<Url>, source: &[u8]) -> FxHashSet<Decl> {
                             documentation: format!("```zeek\n{}\n```", arg.utf8_text(source).ok()?)
                                 .as_str()
                                 .into(),
+                            attrs: attrs(*arg, source),
                         })
                     })
                     .collect();
@@ -640,6 +763,7 @@ pub fn decls_(node: Node, uri: Arc<Url>, source: &[u8]) -> FxHashSet<Decl> {
                                 uri: Arc::clone(&uri),
                             }),
                             documentation,
+                            attrs: attrs(c, source),
 
                             module: ModuleId::None,
                             is_export: None,
@@ -681,6 +805,7 @@ pub fn decls_(node: Node, uri: Arc<Url>, source: &[u8]) -> FxHashSet<Decl> {
                                     uri: Arc::clone(&uri),
                                 }),
                                 documentation,
+                                attrs: attrs(n, source),
                                 // An enum value is exported if its wrapping decl is exported.
                                 is_export: Some(in_export(decl)),
                             })
@@ -769,6 +894,7 @@ pub fn decls_(node: Node, uri: Arc<Url>, source: &[u8]) -> FxHashSet<Decl> {
                                     uri: Arc::clone(&uri),
                                 }),
                                 documentation,
+                                attrs: attrs(c, source),
 
                                 // An enum value is exported if its wrapping decl is exported.
                                 is_export: Some(in_export(decl)),
@@ -819,6 +945,7 @@ pub fn decls_(node: Node, uri: Arc<Url>, source: &[u8]) -> FxHashSet<Decl> {
                         uri: Arc::clone(&uri),
                     }),
                     documentation,
+                    attrs: attrs(decl, source),
                 })
                 .chain(convert(additional_decls.into_iter())),
             )
@@ -949,6 +1076,7 @@ fn modules(node: Node, uri: Arc<Url>, source: &[u8]) -> FxHashSet<Decl> {
                 is_export: None,
                 loc: None,
                 documentation: format!("```zeek\n{id}\n```").as_str().into(),
+                attrs: Vec::new(),
             })
         })
         .cloned()
@@ -1038,6 +1166,7 @@ pub fn fn_param_decls(node: Node, uri: Arc<Url>, source: &[u8]) -> FxHashSet<Dec
                 documentation: format!("```zeek\n{}\n```", arg.utf8_text(source).ok()?)
                     .as_str()
                     .into(),
+                attrs: attrs(arg, source),
             })
         })
         .collect()
@@ -1132,6 +1261,7 @@ fn loop_param_decls(node: Node, uri: &Arc<Url>, source: &[u8]) -> FxHashSet<Decl
                                 uri: Arc::clone(uri),
                             }),
                             documentation,
+                            attrs: Vec::new(),
                         })
                     })
                     .collect(),
@@ -1143,7 +1273,7 @@ fn loop_param_decls(node: Node, uri: &Arc<Url>, source: &[u8]) -> FxHashSet<Decl
 }
 
 #[instrument]
-fn loads_raw<'a>(node: Node, source: &'a str) -> Vec<&'a str> {
+fn loads_raw<'a>(node: Node, source: &'a str) -> Vec<(&'a str, Range)> {
     static QUERY: LazyLock<tree_sitter::Query> = LazyLock::new(|| {
         tree_sitter::Query::new(&language_zeek(), "(\"@load\") (file)@file").expect("invalid query")
     });
@@ -1155,8 +1285,11 @@ fn loads_raw<'a>(node: Node, source: &'a str) -> Vec<&'a str> {
     tree_sitter::QueryCursor::new()
         .matches(&QUERY, node.0, source.as_bytes())
         .filter_map(|c| c.nodes_for_capture_index(c_file).next())
-        .filter_map(|f| f.utf8_text(source.as_bytes()).ok())
         .cloned()
+        .filter_map(|f| {
+            let range = Node::from(f).range();
+            Some((f.utf8_text(source.as_bytes()).ok()?, range))
+        })
         .collect()
 }
 
@@ -1165,8 +1298,9 @@ pub trait Query: Parse {
     #[must_use]
     fn decls(&self, uri: Arc<Url>) -> Arc<[Decl]>;
 
+    /// Get the files loaded with `@load` together with the range of the loaded file.
     #[must_use]
-    fn loads(&self, uri: Arc<Url>) -> Arc<[Str]>;
+    fn loads(&self, uri: Arc<Url>) -> Arc<[(Str, Range)]>;
 
     #[must_use]
     fn function_calls(&self, uri: Arc<Url>) -> Arc<[FunctionCall]>;
@@ -1201,7 +1335,7 @@ fn decls(db: &dyn Query, uri: Arc<Url>) -> Arc<[Decl]> {
 }
 
 #[instrument(skip(db))]
-fn loads(db: &dyn Query, uri: Arc<Url>) -> Arc<[Str]> {
+fn loads(db: &dyn Query, uri: Arc<Url>) -> Arc<[(Str, Range)]> {
     let Some(tree) = db.parse(Arc::clone(&uri)) else {
         return Arc::default();
     };
@@ -1212,9 +1346,8 @@ fn loads(db: &dyn Query, uri: Arc<Url>) -> Arc<[Str]> {
 
     Arc::from(
         loads_raw(tree.root_node(), &source)
-            .iter()
-            .map(ToString::to_string)
-            .map(Str::from)
+            .into_iter()
+            .map(|(load, range)| (Str::from(load), range))
             .collect::<Vec<_>>(),
     )
 }
@@ -1321,6 +1454,7 @@ fn untyped_var_decls(db: &dyn Query, uri: Arc<Url>) -> Arc<[Decl]> {
                         uri: Arc::clone(&uri),
                     }),
                     documentation: empty,
+                    attrs: Vec::new(),
                 })
             })
             .cloned()
@@ -1412,7 +1546,12 @@ mod test {
 
     use std::sync::Arc;
 
-    use crate::{lsp::TestDatabase, parse::Parse, query::Node, Files};
+    use crate::{
+        lsp::TestDatabase,
+        parse::Parse,
+        query::{DeclKind, Node},
+        Files,
+    };
     use insta::assert_debug_snapshot;
     use itertools::Itertools;
     use tower_lsp::lsp_types::{Position, Url};
@@ -1459,6 +1598,9 @@ mod test {
 
         let loads = |source: &'static str| {
             super::loads_raw(parse(source).expect("cannot parse").root_node(), source)
+                .into_iter()
+                .map(|(load, _)| load)
+                .collect::<Vec<_>>()
         };
 
         assert_eq!(loads(""), Vec::<&str>::new());
@@ -1619,4 +1761,47 @@ function f() {}",
             Some("With [link](http://example.com)")
         );
     }
+
+    #[test]
+    fn attrs() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            "const c = 1 &redef;
+type R: record { r: count &optional &default=0; };",
+        );
+
+        let db = db.snapshot();
+        let tree = db.parse(Arc::clone(&uri)).unwrap();
+        let root = tree.root_node();
+        let source = db.source(Arc::clone(&uri)).unwrap();
+
+        let decls = super::decls_(root, uri, source.as_bytes());
+        let attrs = |id: &str| -> Vec<(String, Option<String>)> {
+            let decl = decls.iter().find(|d| d.id == id).unwrap();
+            let attrs = match &decl.kind {
+                DeclKind::Type(fields) => &fields[0].attrs,
+                _ => &decl.attrs,
+            };
+            attrs
+                .iter()
+                .map(|a| {
+                    (
+                        a.name.to_string(),
+                        a.value.as_ref().map(ToString::to_string),
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(attrs("c"), vec![("redef".into(), None)]);
+        assert_eq!(
+            attrs("R"),
+            vec![
+                ("optional".into(), None),
+                ("default".into(), Some("0".into()))
+            ]
+        );
+    }
 }
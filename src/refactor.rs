@@ -0,0 +1,696 @@
+//! Refactorings offered as code actions.
+
+use std::sync::Arc;
+
+use line_index::LineIndex;
+use tower_lsp::lsp_types::{Position, Range, TextEdit, Url};
+
+use crate::{
+    ast::Ast,
+    fix::removal_range,
+    lint,
+    lsp::{file_references, Database},
+    parse::Parse,
+    query::{Decl, DeclKind, ModuleId, Node, NodeLocation},
+    text::{offset, position, text},
+    typecheck, Files,
+};
+
+/// Whether `inner` is contained in `outer`.
+fn contains(outer: Range, inner: Range) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
+/// Shrink the range so it does not start or end with whitespace.
+fn trim(source: &str, range: Range) -> Option<Range> {
+    let index = LineIndex::new(source);
+    let start = offset(&index, range.start)?;
+    let text = source.get(start..offset(&index, range.end)?)?;
+
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let start = start + (text.len() - text.trim_start().len());
+    let end = start + trimmed.len();
+
+    Some(Range::new(position(source, start), position(source, end)))
+}
+
+/// The function, event or hook containing the node.
+fn enclosing_function(node: Node) -> Option<Node> {
+    let mut node = node.parent();
+    while let Some(n) = node {
+        if matches!(n.kind(), "func_decl" | "event_decl" | "hook_decl") {
+            return Some(n);
+        }
+        node = n.parent();
+    }
+    None
+}
+
+/// Find all identifiers below the node which refer to a declaration, i.e., which are not names of
+/// record fields.
+fn ids<'a>(node: Node<'a>, ids_: &mut Vec<Node<'a>>) {
+    if node.kind() == "id" {
+        if !lint::is_field_name(node) {
+            ids_.push(node);
+        }
+        return;
+    }
+
+    for c in node.named_children_not("nl") {
+        ids(c, ids_);
+    }
+}
+
+/// Pick a name based on `base` which does not clash with anything visible at `scope`.
+fn unique_name(db: &Database, base: &str, scope: &NodeLocation) -> String {
+    std::iter::once(base.to_string())
+        .chain((2..100).map(|i| format!("{base}_{i}")))
+        .find(|name| db.resolve_id(name.as_str().into(), scope.clone()).is_none())
+        .unwrap_or_else(|| base.to_string())
+}
+
+/// The whitespace indenting the given line.
+fn indentation(source: &str, line: u32) -> Option<&str> {
+    let line = source.lines().nth(usize::try_from(line).ok()?)?;
+    Some(&line[..line.len() - line.trim_start().len()])
+}
+
+/// The statements making up the selection, or `None` if the selection does not exactly cover
+/// whole statements of a single block.
+fn selected_statements<'a>(root: Node<'a>, source: &str, range: Range) -> Option<Vec<Node<'a>>> {
+    let range = trim(source, range)?;
+
+    let mut node = Some(root.named_descendant_for_point_range(range)?);
+    while let Some(n) = node {
+        let stmts: Vec<_> = n
+            .named_children("stmt")
+            .into_iter()
+            .filter(|s| s.range().start < range.end && range.start < s.range().end)
+            .collect();
+
+        if let (Some(first), Some(last)) = (stmts.first(), stmts.last()) {
+            return (first.range().start == range.start && last.range().end == range.end)
+                .then_some(stmts);
+        }
+
+        node = n.parent();
+    }
+
+    None
+}
+
+/// The start of the node including any `##` zeekygen comments documenting it.
+fn start_with_docs(node: Node) -> Position {
+    let mut start = node.range().start;
+
+    let mut prev = node.prev_sibling();
+    while let Some(comment) = prev.filter(|p| p.kind() == "zeekygen_next_comment") {
+        start = comment.range().start;
+        prev = comment.prev_sibling();
+    }
+
+    start
+}
+
+/// Whether the statement transfers control out of the selection, e.g., with `return`.
+fn has_jump(node: Node) -> bool {
+    node.children().iter().any(|c| {
+        (!c.is_named() && matches!(c.kind(), "return" | "break" | "next" | "fallthrough"))
+            || has_jump(*c)
+    })
+}
+
+/// Whether the decl is a local or parameter declared in the given range.
+fn is_local_to(decl: &Decl, uri: &Arc<Url>, range: Range) -> bool {
+    matches!(decl.kind, DeclKind::Variable | DeclKind::Index(..))
+        && decl
+            .loc
+            .as_ref()
+            .is_some_and(|l| l.uri == *uri && contains(range, l.range))
+}
+
+/// Locals declared in the selection of the function which are used after it.
+fn returned_locals(
+    db: &Database,
+    uri: &Arc<Url>,
+    function: Node,
+    selection: Range,
+) -> Vec<Arc<Decl>> {
+    let mut later_ids = Vec::new();
+    ids(function, &mut later_ids);
+
+    let mut returned: Vec<Arc<Decl>> = Vec::new();
+    for id in later_ids
+        .into_iter()
+        .filter(|id| id.range().start >= selection.end)
+    {
+        let Some(decl) = db.resolve(NodeLocation::from_node(Arc::clone(uri), id)) else {
+            continue;
+        };
+
+        if is_local_to(&decl, uri, selection) && !returned.contains(&decl) {
+            returned.push(decl);
+        }
+    }
+
+    returned
+}
+
+/// A local or parameter of the enclosing function used by extracted code.
+struct Captured {
+    decl: Arc<Decl>,
+
+    /// Name of the declaration's type.
+    typ: String,
+}
+
+/// Extract the selected statements of a function, event or hook body into a new function.
+///
+/// Locals and parameters of the enclosing function used by the statements become parameters of
+/// the new function. If the statements declare a local which is used afterwards it is returned
+/// from the new function.
+pub(crate) fn extract_function(
+    db: &Database,
+    uri: &Arc<Url>,
+    range: Range,
+) -> Option<Vec<TextEdit>> {
+    let source = db.source(Arc::clone(uri))?;
+    let tree = db.parse(Arc::clone(uri))?;
+    let root = tree.root_node();
+
+    let stmts = selected_statements(root, &source, range)?;
+    let first = stmts.first()?;
+    let last = stmts.last()?;
+    let selection = Range::new(first.range().start, last.range().end);
+
+    let function = enclosing_function(*first)?;
+
+    // Control flow leaving the selection cannot be moved into another function.
+    if stmts.iter().any(|s| has_jump(*s)) {
+        return None;
+    }
+
+    let mut selected_ids = Vec::new();
+    for s in &stmts {
+        ids(*s, &mut selected_ids);
+    }
+
+    // Locals and parameters declared outside of the selection become parameters.
+    let mut params: Vec<Captured> = Vec::new();
+    for id in &selected_ids {
+        let Some(decl) = db.resolve(NodeLocation::from_node(Arc::clone(uri), *id)) else {
+            continue;
+        };
+
+        if !is_local_to(&decl, uri, function.range()) || is_local_to(&decl, uri, selection) {
+            continue;
+        }
+
+        // Assignments to parameters would not be visible to the caller.
+        if is_reassigned(*id) {
+            return None;
+        }
+
+        if params.iter().any(|p| p.decl == decl) {
+            continue;
+        }
+
+        let typ = db.typ(Arc::clone(&decl))?.fqid.to_string();
+        params.push(Captured { decl, typ });
+    }
+
+    let returned = match returned_locals(db, uri, function, selection).as_slice() {
+        [] => None,
+        [decl] => Some(Captured {
+            decl: Arc::clone(decl),
+            typ: db.typ(Arc::clone(decl))?.fqid.to_string(),
+        }),
+        _ => return None,
+    };
+
+    // The new function is added before the top-level declaration containing the selection so it
+    // ends up in the same module.
+    let mut top_level = function;
+    while let Some(p) = top_level.parent().filter(|p| p.kind() != "source_file") {
+        top_level = p;
+    }
+
+    let name = unique_name(
+        db,
+        "extracted",
+        &NodeLocation::from_node(Arc::clone(uri), root),
+    );
+
+    let signature = params
+        .iter()
+        .map(|p| format!("{}: {}", p.decl.id, p.typ))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let return_type = returned
+        .as_ref()
+        .map(|r| format!(": {}", r.typ))
+        .unwrap_or_default();
+
+    // Re-indent the statements for the body of the new function.
+    let indent = indentation(&source, selection.start.line)?;
+    let body = text(&source, selection)?
+        .lines()
+        .map(|l| format!("\t{}\n", l.strip_prefix(indent).unwrap_or(l)))
+        .chain(
+            returned
+                .as_ref()
+                .map(|r| format!("\treturn {};\n", r.decl.id)),
+        )
+        .collect::<String>();
+
+    let definition = format!("function {name}({signature}){return_type}\n\t{{\n{body}\t}}\n\n");
+
+    let args = params
+        .iter()
+        .map(|p| p.decl.id.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let call = match &returned {
+        Some(r) => format!("local {} = {name}({args});", r.decl.id),
+        None => format!("{name}({args});"),
+    };
+
+    // Keep documentation of the declaration attached to it.
+    let start = start_with_docs(function).min(start_with_docs(top_level));
+    let at = Position::new(start.line, 0);
+    Some(vec![
+        TextEdit::new(Range::new(at, at), definition),
+        TextEdit::new(selection, call),
+    ])
+}
+
+/// Extract the selected expression into a new local declared before the statement using it.
+pub(crate) fn extract_local(db: &Database, uri: &Arc<Url>, range: Range) -> Option<Vec<TextEdit>> {
+    let source = db.source(Arc::clone(uri))?;
+    let tree = db.parse(Arc::clone(uri))?;
+
+    let range = trim(&source, range)?;
+    let expr = tree
+        .root_node()
+        .named_descendant_for_point_range(range)
+        .filter(|e| e.kind() == "expr" && e.range() == range)?;
+
+    // The target of an assignment cannot be extracted.
+    if expr.parent().is_some_and(|p| {
+        typecheck::is_assignment(p) && p.children().first().map(Node::range) == Some(range)
+    }) {
+        return None;
+    }
+
+    enclosing_function(expr)?;
+
+    let mut stmt = expr.parent();
+    while let Some(s) = stmt.filter(|s| s.kind() != "stmt") {
+        stmt = s.parent();
+    }
+    let stmt = stmt?;
+
+    // The local can only be declared before a statement in a block, and must not move the
+    // expression out of a loop condition evaluated repeatedly.
+    if stmt
+        .parent()
+        .is_some_and(|p| p.kind() == "stmt" && p.children().first().map(Node::kind) != Some("{"))
+        || stmt.children().first().map(Node::kind) == Some("while")
+    {
+        return None;
+    }
+
+    let name = unique_name(db, "x", &NodeLocation::from_node(Arc::clone(uri), stmt));
+    let text = expr.utf8_text(source.as_bytes()).ok()?;
+    let start = stmt.range().start;
+
+    let indent = indentation(&source, start.line)?;
+    let declaration = if indent.len() == start.character as usize {
+        TextEdit::new(
+            Range::new(Position::new(start.line, 0), Position::new(start.line, 0)),
+            format!("{indent}local {name} = {text};\n"),
+        )
+    } else {
+        TextEdit::new(Range::new(start, start), format!("local {name} = {text}; "))
+    };
+
+    Some(vec![declaration, TextEdit::new(range, name)])
+}
+
+/// Whether the expression needs parentheses when used as an operand, e.g., `a + b` but not `f(a)`.
+fn is_compound(expr: Node) -> bool {
+    let children = expr.children();
+
+    // Parenthesized expressions are already atomic.
+    if children.first().map(Node::kind) == Some("(") && children.last().map(Node::kind) == Some(")")
+    {
+        return false;
+    }
+
+    typecheck::call_target(expr).is_none()
+        && children.iter().any(|c| {
+            !c.is_named() && !matches!(c.kind(), "(" | ")" | "[" | "]" | "$" | "?$" | "," | "|")
+        })
+}
+
+/// Whether a compound expression replacing `expr` needs to be parenthesized.
+///
+/// This is the case unless `expr` is used on its own, e.g., as a statement, initializer, call
+/// argument or right-hand side of an assignment.
+fn needs_parens(expr: Node) -> bool {
+    let Some(parent) = expr.parent().filter(|p| p.kind() == "expr") else {
+        return false;
+    };
+
+    let is = |n: &Node| n.range() == expr.range();
+
+    match parent.children().as_slice() {
+        [open, inner, close] if open.kind() == "(" && close.kind() == ")" && is(inner) => false,
+        [_, op, rhs] if op.kind() == "=" && is(rhs) => false,
+        _ => typecheck::call_target(parent).is_none(),
+    }
+}
+
+/// Whether the identifier is assigned to, e.g., in `x = 1`, `x += 1` or `++x`.
+fn is_reassigned(id: Node) -> bool {
+    if lint::is_write(id) {
+        return true;
+    }
+
+    let Some(expr) = id.parent().filter(|p| p.kind() == "expr") else {
+        return false;
+    };
+
+    expr.parent()
+        .is_some_and(|p| match p.children().as_slice() {
+            [lhs, op, _] => matches!(op.kind(), "+=" | "-=") && lhs.range() == expr.range(),
+            [a, b] => [(a, b), (b, a)].iter().any(|(op, operand)| {
+                matches!(op.kind(), "++" | "--") && operand.range() == expr.range()
+            }),
+            _ => false,
+        })
+}
+
+/// Whether evaluating the expression can have side effects, e.g., because it calls a function.
+fn has_side_effects(expr: Node) -> bool {
+    typecheck::call_target(expr).is_some()
+        || expr.children().iter().any(|c| {
+            (!c.is_named() && matches!(c.kind(), "=" | "+=" | "-=" | "++" | "--" | "schedule"))
+                || has_side_effects(*c)
+        })
+}
+
+/// Whether a variable read by the expression is assigned to in the given range of the scope.
+fn reads_reassigned(db: &Database, uri: &Arc<Url>, expr: Node, scope: Node, range: Range) -> bool {
+    let resolve = |id: Node| db.resolve(NodeLocation::from_node(Arc::clone(uri), id));
+
+    let mut read = Vec::new();
+    ids(expr, &mut read);
+    let read: Vec<_> = read.into_iter().filter_map(resolve).collect();
+
+    let mut written = Vec::new();
+    ids(scope, &mut written);
+    written
+        .into_iter()
+        .filter(|id| contains(range, id.range()) && is_reassigned(*id))
+        .filter_map(resolve)
+        .any(|decl| read.contains(&decl))
+}
+
+/// Inline the `local` or `const` at the position into all its references in the file.
+///
+/// Returns a title for the refactoring together with its edits. Variables which are assigned to
+/// after their declaration are not inlined.
+pub(crate) fn inline(
+    db: &Database,
+    uri: &Arc<Url>,
+    position: Position,
+) -> Option<(String, Vec<TextEdit>)> {
+    let source = db.source(Arc::clone(uri))?;
+    let tree = db.parse(Arc::clone(uri))?;
+    let root = tree.root_node();
+
+    let node = root.named_descendant_for_position(position)?;
+    let decl = db.resolve(NodeLocation::from_node(Arc::clone(uri), node))?;
+    let loc = decl.loc.as_ref().filter(|l| l.uri == *uri)?;
+
+    let decl_node = root.named_descendant_for_point_range(loc.range)?;
+    let what = match (decl_node.kind(), &decl.kind) {
+        ("var_decl", DeclKind::Variable)
+            if decl_node.children().first().map(Node::kind) == Some("local") =>
+        {
+            "local"
+        }
+        // Constants which can be used from other files cannot be inlined.
+        ("const_decl", DeclKind::Const)
+            if decl.is_export != Some(true)
+                && !matches!(decl.module, ModuleId::Global | ModuleId::None)
+                && !decl.attrs.iter().any(|a| a.name == "redef") =>
+        {
+            "constant"
+        }
+        _ => return None,
+    };
+
+    if what == "constant"
+        && db
+            .files()
+            .iter()
+            .filter(|f| **f != *uri)
+            .any(|f| !file_references(db, Arc::clone(f), &decl).is_empty())
+    {
+        return None;
+    }
+
+    let init = decl_node.named_child("initializer")?.named_child("expr")?;
+    let text = init.utf8_text(source.as_bytes()).ok()?;
+
+    // Skip the declaration itself.
+    let references: Vec<_> = file_references(db, Arc::clone(uri), &decl)
+        .into_iter()
+        .filter(|r| !contains(loc.range, r.range))
+        .collect();
+
+    // The initializer must evaluate to the same value at each reference as at the declaration.
+    if references.len() > 1 && has_side_effects(init) {
+        return None;
+    }
+    let last = references
+        .iter()
+        .map(|r| r.range.end)
+        .max()
+        .unwrap_or(loc.range.end);
+    let scope = enclosing_function(decl_node).unwrap_or(root);
+    if reads_reassigned(
+        db,
+        uri,
+        init,
+        scope,
+        Range::new(decl_node.range().end, last),
+    ) {
+        return None;
+    }
+
+    let mut edits = Vec::new();
+    for reference in references {
+        let id = root.named_descendant_for_point_range(reference.range)?;
+        if is_reassigned(id) {
+            return None;
+        }
+
+        let replacement = if is_compound(init)
+            && id
+                .parent()
+                .filter(|e| e.kind() == "expr")
+                .is_some_and(needs_parens)
+        {
+            format!("({text})")
+        } else {
+            text.to_string()
+        };
+
+        edits.push(TextEdit::new(reference.range, replacement));
+    }
+
+    edits.sort_by_key(|e| e.range.start);
+    edits.insert(
+        0,
+        TextEdit::new(removal_range(&source, decl_node.range()), String::new()),
+    );
+
+    Some((format!("Inline {what} '{}'", decl.id), edits))
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::sync::Arc;
+
+    use tower_lsp::lsp_types::{Position, Range, Url};
+
+    use crate::{fix::apply, lsp::TestDatabase};
+
+    fn database(source: &str) -> (TestDatabase, Arc<Url>) {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), source);
+        (db, uri)
+    }
+
+    #[test]
+    fn trim() {
+        assert_eq!(
+            super::trim(
+                "a\n  b c  \nd",
+                Range::new(Position::new(1, 0), Position::new(2, 0))
+            ),
+            Some(Range::new(Position::new(1, 2), Position::new(1, 5)))
+        );
+        assert_eq!(
+            super::trim("a  b", Range::new(Position::new(0, 1), Position::new(0, 3))),
+            None
+        );
+    }
+
+    #[test]
+    fn extract_function() {
+        let source = "module M;
+
+## Handles e.
+event e(c: count)
+\t{
+\tlocal s = \"x\";
+\tlocal n = c + 1;
+\tprint s, n;
+\tprint n;
+\t}";
+        let (db, uri) = database(source);
+
+        let edits = super::extract_function(
+            &db.0,
+            &uri,
+            Range::new(Position::new(6, 0), Position::new(7, 12)),
+        )
+        .unwrap();
+
+        assert_eq!(
+            apply(source, &edits),
+            "module M;
+
+function extracted(c: count, s: string): count
+\t{
+\tlocal n = c + 1;
+\tprint s, n;
+\treturn n;
+\t}
+
+## Handles e.
+event e(c: count)
+\t{
+\tlocal s = \"x\";
+\tlocal n = extracted(c, s);
+\tprint n;
+\t}"
+        );
+
+        // Updates of captured locals would not be visible to the caller.
+        let (db, uri) = database("event e()\n\t{\n\tlocal n = 0;\n\tn += 1;\n\tprint n;\n\t}");
+        assert_eq!(
+            super::extract_function(
+                &db.0,
+                &uri,
+                Range::new(Position::new(3, 0), Position::new(3, 8))
+            ),
+            None
+        );
+
+        // Statements returning from the enclosing function cannot be extracted.
+        let (db, uri) = database("function f(): count\n\t{\n\treturn 1;\n\t}");
+        assert_eq!(
+            super::extract_function(
+                &db.0,
+                &uri,
+                Range::new(Position::new(2, 0), Position::new(2, 10))
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn extract_local() {
+        let source = "event e(c: count)\n\t{\n\tprint c + 1;\n\t}";
+        let (db, uri) = database(source);
+
+        let edits = super::extract_local(
+            &db.0,
+            &uri,
+            Range::new(Position::new(2, 7), Position::new(2, 12)),
+        )
+        .unwrap();
+
+        assert_eq!(
+            apply(source, &edits),
+            "event e(c: count)\n\t{\n\tlocal x = c + 1;\n\tprint x;\n\t}"
+        );
+    }
+
+    #[test]
+    fn inline() {
+        let source =
+            "module M;\nconst c = 1 + 2;\nevent e()\n\t{\n\tlocal x = c;\n\tprint c * 2, x;\n\t}";
+        let (mut db, uri) = database(source);
+
+        let (title, edits) = super::inline(&db.0, &uri, Position::new(1, 6)).unwrap();
+        assert_eq!(title, "Inline constant 'c'");
+        assert_eq!(
+            apply(source, &edits),
+            "module M;\nevent e()\n\t{\n\tlocal x = 1 + 2;\n\tprint (1 + 2) * 2, x;\n\t}"
+        );
+
+        let (title, edits) = super::inline(&db.0, &uri, Position::new(5, 14)).unwrap();
+        assert_eq!(title, "Inline local 'x'");
+        assert_eq!(
+            apply(source, &edits),
+            "module M;\nconst c = 1 + 2;\nevent e()\n\t{\n\tprint c * 2, c;\n\t}"
+        );
+
+        // Constants used from other files are not inlined.
+        db.add_file(
+            Url::from_file_path("/y.zeek").unwrap(),
+            "@load ./x\nmodule M;\nprint c;",
+        );
+        assert_eq!(super::inline(&db.0, &uri, Position::new(1, 6)), None);
+
+        // Constants in the global namespace are not inlined.
+        let (db, uri) = database("const c = 1;\nprint c;");
+        assert_eq!(super::inline(&db.0, &uri, Position::new(0, 6)), None);
+
+        // Variables which are reassigned are not inlined.
+        let (db, uri) = database("event e()\n\t{\n\tlocal x = 1;\n\tx = 2;\n\tprint x;\n\t}");
+        assert_eq!(super::inline(&db.0, &uri, Position::new(2, 7)), None);
+
+        // Initializers with side effects are not evaluated more than once.
+        let (db, uri) = database("event e()\n\t{\n\tlocal t = network_time();\n\tprint t, t;\n\t}");
+        assert_eq!(super::inline(&db.0, &uri, Position::new(2, 7)), None);
+
+        // Variables read by the initializer must not change before the last use.
+        let (db, uri) = database(
+            "event e()\n\t{\n\tlocal x = 1;\n\tlocal old = x;\n\tx = 2;\n\tprint old;\n\t}",
+        );
+        assert_eq!(super::inline(&db.0, &uri, Position::new(3, 7)), None);
+
+        // Compound expressions are parenthesized when indexed or accessed.
+        let source = "event e()\n\t{\n\tlocal s = \"a\" + \"b\";\n\tprint s[0];\n\t}";
+        let (db, uri) = database(source);
+        let (_, edits) = super::inline(&db.0, &uri, Position::new(2, 7)).unwrap();
+        assert_eq!(
+            apply(source, &edits),
+            "event e()\n\t{\n\tprint (\"a\" + \"b\")[0];\n\t}"
+        );
+    }
+}
@@ -0,0 +1,417 @@
+//! Support for Zeek's signature language, see
+//! <https://docs.zeek.org/en/master/frameworks/signatures.html>.
+
+use std::{path::Path, sync::Arc};
+
+use tower_lsp::lsp_types::{Position, Range, Url};
+use tracing::instrument;
+
+use crate::{parse::Parse, Str};
+
+/// Known signature attributes together with whether they take arguments.
+pub const ATTRIBUTES: &[(&str, bool)] = &[
+    ("dst-ip", true),
+    ("dst-port", true),
+    ("enable", true),
+    ("eval", true),
+    ("event", true),
+    ("file-magic", true),
+    ("file-mime", true),
+    ("finger", true),
+    ("ftp", true),
+    ("header", true),
+    ("http", true),
+    ("http-reply-body", true),
+    ("http-reply-header", true),
+    ("http-request", true),
+    ("http-request-body", true),
+    ("http-request-header", true),
+    ("ip-options", true),
+    ("ip-proto", true),
+    ("payload", true),
+    ("payload-size", true),
+    ("requires-reverse-signature", true),
+    ("requires-signature", true),
+    ("same-ip", false),
+    ("src-ip", true),
+    ("src-port", true),
+    ("tcp-state", true),
+    ("udp-state", true),
+];
+
+/// Check whether a file is a signature file.
+#[must_use]
+pub fn is_sig_file(uri: &Url) -> bool {
+    Path::new(uri.path()).extension() == Some("sig".as_ref())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Word,
+    String,
+    Regex,
+    LBrace,
+    RBrace,
+    Semicolon,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: Str,
+    pub range: Range,
+}
+
+/// An attribute of a signature, e.g., `payload /^GET/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub name: Token,
+    pub args: Vec<Token>,
+}
+
+impl Attribute {
+    /// Get the first argument of the attribute, ignoring any negation.
+    #[must_use]
+    pub fn value(&self) -> Option<&Token> {
+        self.args.iter().find(|a| a.text != "!")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub id: Str,
+    pub range: Range,
+    pub selection_range: Range,
+    pub attributes: Vec<Attribute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub range: Range,
+    pub message: String,
+}
+
+/// A parsed signature file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct File {
+    pub signatures: Vec<Signature>,
+    pub errors: Vec<Error>,
+}
+
+impl File {
+    /// Get the attribute whose name or arguments contain the position.
+    #[must_use]
+    pub fn attribute_at(&self, position: Position) -> Option<&Attribute> {
+        self.signatures
+            .iter()
+            .flat_map(|s| &s.attributes)
+            .find(|a| {
+                std::iter::once(&a.name)
+                    .chain(&a.args)
+                    .any(|t| contains(t.range, position))
+            })
+    }
+}
+
+fn contains(range: Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
+
+#[instrument(skip(db))]
+pub(crate) fn parse_sig(db: &dyn Parse, file: Arc<Url>) -> Option<Arc<File>> {
+    if !is_sig_file(&file) {
+        return None;
+    }
+
+    let source = db.source(file)?;
+    Some(Arc::new(parse(&source)))
+}
+
+/// Parse the source of a signature file.
+#[must_use]
+pub fn parse(source: &str) -> File {
+    let (tokens, mut errors) = tokenize(source);
+    let mut signatures = Vec::new();
+
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        if token.kind != TokenKind::Word || token.text != "signature" {
+            errors.push(Error {
+                range: token.range,
+                message: format!("expected 'signature', found '{}'", token.text),
+            });
+
+            // Skip to the next signature.
+            while tokens
+                .next_if(|t| t.kind != TokenKind::Word || t.text != "signature")
+                .is_some()
+            {}
+            continue;
+        }
+
+        let Some(id) = tokens.next_if(|t| t.kind == TokenKind::Word) else {
+            errors.push(Error {
+                range: token.range,
+                message: "expected signature id".into(),
+            });
+            continue;
+        };
+
+        if tokens.next_if(|t| t.kind == TokenKind::LBrace).is_none() {
+            errors.push(Error {
+                range: id.range,
+                message: format!("expected '{{' after signature id '{}'", id.text),
+            });
+            continue;
+        }
+
+        let mut attributes = Vec::new();
+        let mut end = None;
+
+        while let Some(t) = tokens.next() {
+            match t.kind {
+                TokenKind::RBrace => {
+                    end = Some(t.range.end);
+                    break;
+                }
+                TokenKind::Semicolon => {}
+                TokenKind::Word => {
+                    let Some((_, takes_args)) = ATTRIBUTES.iter().find(|(a, _)| *a == t.text)
+                    else {
+                        errors.push(Error {
+                            range: t.range,
+                            message: format!("unknown signature attribute '{}'", t.text),
+                        });
+                        continue;
+                    };
+
+                    // Arguments extend to the end of the line or the next `;` or `}`.
+                    let line = t.range.end.line;
+                    let mut args = Vec::new();
+                    while let Some(arg) = tokens.next_if(|a| {
+                        a.range.start.line == line
+                            && !matches!(a.kind, TokenKind::Semicolon | TokenKind::RBrace)
+                    }) {
+                        args.push(arg);
+                    }
+
+                    if *takes_args && args.is_empty() {
+                        errors.push(Error {
+                            range: t.range,
+                            message: format!("missing value for '{}'", t.text),
+                        });
+                    }
+
+                    attributes.push(Attribute { name: t, args });
+                }
+                _ => {
+                    errors.push(Error {
+                        range: t.range,
+                        message: format!("unexpected '{}'", t.text),
+                    });
+                }
+            }
+        }
+
+        let Some(end) = end else {
+            errors.push(Error {
+                range: id.range,
+                message: format!("missing '}}' for signature '{}'", id.text),
+            });
+            signatures.push(Signature {
+                range: Range::new(token.range.start, id.range.end),
+                selection_range: id.range,
+                id: id.text,
+                attributes,
+            });
+            break;
+        };
+
+        signatures.push(Signature {
+            range: Range::new(token.range.start, end),
+            selection_range: id.range,
+            id: id.text,
+            attributes,
+        });
+    }
+
+    File { signatures, errors }
+}
+
+fn tokenize(source: &str) -> (Vec<Token>, Vec<Error>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+
+    for (line, text) in source.lines().enumerate() {
+        let Ok(line) = u32::try_from(line) else {
+            break;
+        };
+
+        let chars: Vec<_> = text.chars().collect();
+        let position = |col: usize| Position::new(line, u32::try_from(col).unwrap_or(u32::MAX));
+
+        let mut col = 0;
+        while col < chars.len() {
+            let c = chars[col];
+            let start = col;
+
+            let kind = match c {
+                c if c.is_whitespace() => {
+                    col += 1;
+                    continue;
+                }
+                '#' => break,
+                '{' => TokenKind::LBrace,
+                '}' => TokenKind::RBrace,
+                ';' => TokenKind::Semicolon,
+                '"' | '/' => {
+                    // Strings and regular expressions extend to the next unescaped delimiter.
+                    col += 1;
+                    while col < chars.len() && chars[col] != c {
+                        if chars[col] == '\\' {
+                            col += 1;
+                        }
+                        col += 1;
+                    }
+
+                    if col >= chars.len() {
+                        errors.push(Error {
+                            range: Range::new(position(start), position(chars.len())),
+                            message: if c == '"' {
+                                "unterminated string".into()
+                            } else {
+                                "unterminated regular expression".into()
+                            },
+                        });
+                        break;
+                    }
+
+                    if c == '"' {
+                        TokenKind::String
+                    } else {
+                        // Skip any flags of the regular expression.
+                        while col + 1 < chars.len() && chars[col + 1].is_ascii_alphabetic() {
+                            col += 1;
+                        }
+                        TokenKind::Regex
+                    }
+                }
+                _ => {
+                    while col + 1 < chars.len()
+                        && !chars[col + 1].is_whitespace()
+                        && !matches!(chars[col + 1], '{' | '}' | ';' | '"' | '#')
+                    {
+                        col += 1;
+                    }
+                    TokenKind::Word
+                }
+            };
+
+            col += 1;
+            tokens.push(Token {
+                kind,
+                text: chars[start..col].iter().collect::<String>().into(),
+                range: Range::new(position(start), position(col)),
+            });
+        }
+    }
+
+    (tokens, errors)
+}
+
+/// Get the signature files loaded with `@load-sigs` in a Zeek script.
+#[must_use]
+pub fn load_sigs(source: &str) -> Vec<&str> {
+    source
+        .lines()
+        .filter_map(|l| l.trim_start().strip_prefix("@load-sigs"))
+        .filter_map(|l| l.split_whitespace().next())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use tower_lsp::lsp_types::Position;
+
+    use crate::sig::{load_sigs, parse, TokenKind};
+
+    const SOURCE: &str = r#"# A comment.
+signature my-sig {
+    ip-proto == tcp
+    payload /^GET \/ HTTP/i
+    event "found it"
+    eval MyModule::check
+}
+
+signature other { requires-signature ! my-sig; same-ip; }
+"#;
+
+    #[test]
+    fn signatures() {
+        let file = parse(SOURCE);
+        assert_eq!(file.errors, []);
+
+        assert_eq!(
+            file.signatures
+                .iter()
+                .map(|s| s.id.as_str())
+                .collect::<Vec<_>>(),
+            ["my-sig", "other"]
+        );
+
+        let sig = &file.signatures[0];
+        assert_eq!(sig.range.start, Position::new(1, 0));
+        assert_eq!(sig.range.end, Position::new(6, 1));
+        assert_eq!(
+            sig.attributes
+                .iter()
+                .map(|a| a.name.text.as_str())
+                .collect::<Vec<_>>(),
+            ["ip-proto", "payload", "event", "eval"]
+        );
+        assert_eq!(sig.attributes[1].args[0].kind, TokenKind::Regex);
+        assert_eq!(sig.attributes[2].args[0].kind, TokenKind::String);
+        assert_eq!(sig.attributes[3].value().unwrap().text, "MyModule::check");
+
+        let other = &file.signatures[1];
+        assert_eq!(other.attributes[0].value().unwrap().text, "my-sig");
+        assert!(other.attributes[1].args.is_empty());
+
+        let attr = file.attribute_at(Position::new(5, 12)).unwrap();
+        assert_eq!(attr.name.text, "eval");
+    }
+
+    #[test]
+    fn errors() {
+        let file = parse(
+            "signature s {\n  payload\n  foo bar\n  event \"abc\n}\nsig x {}\nsignature t {\n",
+        );
+
+        assert_eq!(
+            file.errors
+                .iter()
+                .map(|e| e.message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "unterminated string",
+                "missing value for 'payload'",
+                "unknown signature attribute 'foo'",
+                "unknown signature attribute 'bar'",
+                "missing value for 'event'",
+                "expected 'signature', found 'sig'",
+                "missing '}' for signature 't'",
+            ]
+        );
+    }
+
+    #[test]
+    fn loads() {
+        assert_eq!(
+            load_sigs("@load base/x\n@load-sigs ./dpd.sig\n  @load-sigs other # c\n"),
+            ["./dpd.sig", "other"]
+        );
+    }
+}
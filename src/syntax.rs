@@ -0,0 +1,328 @@
+//! Diagnostics for syntax errors.
+
+use itertools::Itertools;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range};
+
+use crate::{lsp::ERROR_CODE_IS_MISSING, query::Node};
+
+/// Computes diagnostics for syntax errors in the tree of the given source.
+///
+/// Errors are explained in terms of the construct being parsed. Since an error usually causes
+/// further errors in the same statement or declaration only the first error of each is reported.
+pub(crate) fn diagnostics(root: Node, source: &str) -> Vec<Diagnostic> {
+    let mut groups = Vec::new();
+
+    let mut diags: Vec<_> = root
+        .errors()
+        .into_iter()
+        .sorted_by_key(|e| (e.range().start.line, e.range().start.character))
+        .filter_map(|err| {
+            let group = group(err);
+            if groups.contains(&group) {
+                return None;
+            }
+            groups.push(group);
+
+            Some((err, error(err, source)))
+        })
+        .collect();
+
+    // If the source has unbalanced braces, explain the error they caused in terms of them.
+    if let Some(brace) = unbalanced_brace(root) {
+        let caused = if brace.open {
+            diags
+                .iter()
+                .rposition(|(e, _)| e.is_missing() && e.kind() == "}")
+                .or_else(|| {
+                    diags
+                        .iter()
+                        .rposition(|(e, _)| e.range().end >= brace.position)
+                })
+        } else {
+            diags.iter().position(|(e, _)| {
+                e.range().start <= brace.position && brace.position < e.range().end
+            })
+        };
+
+        if let Some(i) = caused {
+            diags[i].1.message = if brace.open {
+                format!("unbalanced `{{` opened at line {}", brace.position.line + 1)
+            } else {
+                "unbalanced `}` without matching `{`".into()
+            };
+        }
+    }
+
+    let mut diags: Vec<_> = diags.into_iter().map(|(_, d)| d).collect();
+    top_level_locals(root, &mut diags);
+    diags
+}
+
+/// The range of the statement or declaration an error belongs to.
+///
+/// Errors inside other errors belong to the outermost error.
+fn group(err: Node) -> Range {
+    let mut group = None;
+    let mut outermost_error = None;
+
+    let mut node = err.parent();
+    while let Some(n) = node {
+        if n.kind() == "ERROR" {
+            outermost_error = Some(n);
+        } else if group.is_none() && is_statement(n) {
+            group = Some(n);
+        }
+        node = n.parent();
+    }
+
+    outermost_error.or(group).unwrap_or(err).range()
+}
+
+/// Whether the node is a statement or declaration.
+fn is_statement(node: Node) -> bool {
+    node.kind() == "stmt"
+        || node.kind().ends_with("_decl")
+        || node.parent().is_some_and(|p| p.kind() == "source_file")
+}
+
+/// Computes the diagnostic for an `ERROR` or `MISSING` node.
+fn error(err: Node, source: &str) -> Diagnostic {
+    let context = context(err);
+
+    let (message, code) = if err.is_missing() {
+        let what = match err.kind() {
+            "id" => "identifier".to_string(),
+            "expr" => "expression".to_string(),
+            "type" => "type".to_string(),
+            token => format!("`{token}`"),
+        };
+
+        let message = match (err.kind(), context) {
+            (";", Some(context)) => format!("missing {what} after {context}"),
+            (_, Some(context)) => format!("missing {what} in {context}"),
+            (_, None) => format!("missing {what}"),
+        };
+
+        (message, Some(NumberOrString::Number(ERROR_CODE_IS_MISSING)))
+    } else {
+        let token = err
+            .children()
+            .first()
+            .and_then(|c| c.utf8_text(source.as_bytes()).ok())
+            .and_then(|t| t.split_whitespace().next())
+            .map(|t| t.chars().take(20).collect::<String>());
+
+        let message = match (token.as_deref(), context) {
+            (Some("local"), None) => {
+                "`local` is not allowed at top level, use `global` instead".to_string()
+            }
+            (Some(token), Some(context)) => format!("unexpected `{token}` in {context}"),
+            (Some(token), None) => format!("unexpected `{token}` at top level"),
+            (None, _) => err.error().to_string(),
+        };
+
+        (message, None)
+    };
+
+    Diagnostic::new(
+        err.range(),
+        Some(DiagnosticSeverity::ERROR),
+        code,
+        None,
+        message,
+        None,
+        None,
+    )
+}
+
+/// Describes the construct which was being parsed when the error occurred, or `None` at top level.
+fn context(err: Node) -> Option<&'static str> {
+    let mut node = err.parent();
+    while let Some(n) = node {
+        let context = match n.kind() {
+            "source_file" => return None,
+            "stmt" => "statement",
+            "expr" => "expression",
+            "var_decl" => "variable declaration",
+            "const_decl" => "constant declaration",
+            "option_decl" => "option declaration",
+            "type_decl" => "type declaration",
+            "redef_enum_decl" | "redef_record_decl" => "redef",
+            "func_decl" => "function",
+            "event_decl" => "event handler",
+            "hook_decl" => "hook handler",
+            "export_decl" => "export block",
+            "module_decl" => "module declaration",
+            "func_params" | "formal_args" => "parameter list",
+            "attr_list" => "attributes",
+            _ => {
+                node = n.parent();
+                continue;
+            }
+        };
+
+        return Some(context);
+    }
+
+    None
+}
+
+/// Report `local` declarations outside of functions, events and hooks.
+fn top_level_locals(node: Node, diags: &mut Vec<Diagnostic>) {
+    match node.kind() {
+        "func_decl" | "event_decl" | "hook_decl" | "ERROR" => return,
+        "var_decl" => {
+            if let Some(local) = node.children().first().filter(|c| c.kind() == "local") {
+                diags.push(Diagnostic::new(
+                    local.range(),
+                    Some(DiagnosticSeverity::ERROR),
+                    None,
+                    None,
+                    "`local` is not allowed at top level, use `global` instead".into(),
+                    None,
+                    None,
+                ));
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    for c in node.named_children_not("nl") {
+        top_level_locals(c, diags);
+    }
+}
+
+/// A brace without a matching counterpart.
+struct Brace {
+    position: Position,
+
+    /// Whether this is an opening brace.
+    open: bool,
+}
+
+/// Finds the innermost unclosed `{`, or the first `}` without matching `{`.
+///
+/// Braces are taken from the tokens of the tree so braces in comments, strings or patterns are
+/// ignored. `}` inserted by the parser during error recovery do not close anything.
+fn unbalanced_brace(root: Node) -> Option<Brace> {
+    fn collect_braces<'t>(node: Node<'t>, braces: &mut Vec<Node<'t>>) {
+        match node.kind() {
+            "{" | "}" => {
+                if !node.is_missing() {
+                    braces.push(node);
+                }
+            }
+            _ => {
+                for c in node.children() {
+                    collect_braces(c, braces);
+                }
+            }
+        }
+    }
+
+    let mut braces = Vec::new();
+    collect_braces(root, &mut braces);
+
+    let mut open = Vec::new();
+    for brace in braces {
+        if brace.kind() == "{" {
+            open.push(brace);
+        } else if open.pop().is_none() {
+            return Some(Brace {
+                position: brace.range().start,
+                open: false,
+            });
+        }
+    }
+
+    open.last().map(|brace| Brace {
+        position: brace.range().start,
+        open: true,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::sync::Arc;
+
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Url};
+
+    use crate::{lsp::TestDatabase, parse::Parse};
+
+    fn diagnostics(source: &str) -> Vec<(u32, String)> {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), source);
+
+        let tree = db.0.parse(uri).unwrap();
+        let diags = super::diagnostics(tree.root_node(), source);
+        assert!(diags
+            .iter()
+            .all(|d| d.severity == Some(DiagnosticSeverity::ERROR)));
+
+        diags
+            .into_iter()
+            .map(|d| (d.range.start.line, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn unbalanced_brace() {
+        let unbalanced_brace = |source: &str| {
+            let mut db = TestDatabase::default();
+            let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+            db.add_file((*uri).clone(), source);
+
+            let tree = db.0.parse(uri).unwrap();
+            super::unbalanced_brace(tree.root_node()).map(|b| (b.open, b.position))
+        };
+
+        assert_eq!(
+            unbalanced_brace("event e() {\n  if ( T ) {\n  }\n"),
+            Some((true, Position::new(0, 10)))
+        );
+
+        assert_eq!(
+            unbalanced_brace("event e() {\n}\n}"),
+            Some((false, Position::new(2, 0)))
+        );
+
+        assert_eq!(unbalanced_brace("# {\nprint \"{\";"), None);
+        assert_eq!(unbalanced_brace("global p = /[{]/;"), None);
+    }
+
+    #[test]
+    fn missing_semicolon() {
+        assert_eq!(
+            diagnostics("global x = 42"),
+            [(0, "missing `;` after variable declaration".to_string())]
+        );
+    }
+
+    #[test]
+    fn unclosed_brace() {
+        assert_eq!(
+            diagnostics("event e() {\n    print 1;\n"),
+            [(2, "unbalanced `{` opened at line 1".to_string())]
+        );
+    }
+
+    #[test]
+    fn top_level_local() {
+        assert_eq!(
+            diagnostics("local x = 1;"),
+            [(
+                0,
+                "`local` is not allowed at top level, use `global` instead".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn cascading_errors() {
+        assert_eq!(diagnostics("event e() {\n    print 1 2 3 4;\n}").len(), 1);
+    }
+}
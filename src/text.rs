@@ -0,0 +1,31 @@
+//! Helpers for working with positions and offsets in source text.
+
+use line_index::{LineCol, LineIndex};
+use tower_lsp::lsp_types::{Position, Range};
+
+/// Get the position of a byte offset in the source.
+pub(crate) fn position(source: &str, offset: usize) -> Position {
+    let before = &source[..offset];
+    let line = before.matches('\n').count();
+    let column = before.rfind('\n').map_or(offset, |n| offset - n - 1);
+    Position::new(
+        u32::try_from(line).unwrap_or(u32::MAX),
+        u32::try_from(column).unwrap_or(u32::MAX),
+    )
+}
+
+/// The byte offset of a position in the source.
+pub(crate) fn offset(index: &LineIndex, position: Position) -> Option<usize> {
+    index
+        .offset(LineCol {
+            line: position.line,
+            col: position.character,
+        })
+        .map(usize::from)
+}
+
+/// The source text in the given range.
+pub(crate) fn text(source: &str, range: Range) -> Option<&str> {
+    let index = LineIndex::new(source);
+    source.get(offset(&index, range.start)?..offset(&index, range.end)?)
+}
@@ -0,0 +1,1250 @@
+use std::sync::Arc;
+
+use itertools::Itertools;
+use tower_lsp::lsp_types::{Diagnostic, Position, Range, TextEdit, Url};
+
+use crate::{
+    ast::Ast,
+    lint::{Lint, Rule},
+    lsp::Database,
+    parse::Parse,
+    query::{self, Decl, DeclKind, Node, NodeLocation, Query, Signature, Type},
+    Files, Str,
+};
+
+/// Maximum depth up to which we follow untyped declarations to their initializers.
+const MAX_DEPTH: usize = 8;
+
+/// Type mismatches in assignments, calls, returns and attributes.
+///
+/// The checks here are deliberately conservative: we only report mismatches between types we
+/// can reliably compare, i.e., atomic builtin types, enums and records. Anything else is assumed
+/// to be compatible.
+pub(crate) struct TypeMismatch;
+
+impl Lint for TypeMismatch {
+    fn rule(&self) -> Rule {
+        Rule::TypeMismatch
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        let Some(tree) = db.parse(Arc::clone(uri)) else {
+            return Vec::new();
+        };
+
+        let mut diags = Vec::new();
+        visit(db, uri, tree.root_node(), &mut diags);
+        diags
+    }
+}
+
+/// Event and hook handlers whose parameters do not match any of their declarations.
+pub(crate) struct HandlerSignature;
+
+impl Lint for HandlerSignature {
+    fn rule(&self) -> Rule {
+        Rule::HandlerSignature
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_handlers(db, uri)
+    }
+}
+
+fn visit(db: &Database, uri: &Arc<Url>, node: Node, diags: &mut Vec<Diagnostic>) {
+    // Do not attempt to make sense of code with syntax errors.
+    if node.kind() == "ERROR" {
+        return;
+    }
+
+    match node.kind() {
+        "var_decl" | "const_decl" | "option_decl" => diags.extend(check_initializer(db, uri, node)),
+        "expr" if is_assignment(node) => diags.extend(check_assignment(db, uri, node)),
+        "attr" => diags.extend(check_attr(db, uri, node)),
+        _ => {}
+    }
+
+    if let Some(callee) = call_target(node) {
+        diags.extend(check_call(db, uri, node, callee));
+    }
+
+    if is_return(node) {
+        diags.extend(check_return(db, uri, node));
+    }
+
+    for c in node.named_children_not("nl") {
+        visit(db, uri, c, diags);
+    }
+}
+
+/// Checks that the initializer of a typed declaration matches the declared type.
+fn check_initializer(db: &Database, uri: &Arc<Url>, node: Node) -> Option<Diagnostic> {
+    let typ = node.named_child("type")?;
+    let init = node.named_child("initializer")?.named_child("expr")?;
+
+    let expected = type_of_type_node(db, uri, typ)?;
+    let actual = expr_type(db, uri, init, 0)?;
+
+    mismatch(init, &expected, &actual, "initialization")
+}
+
+/// Fields added with `redef record` which are neither `&optional` nor have a `&default`.
+pub(crate) struct RedefFieldNotOptional;
+
+impl Lint for RedefFieldNotOptional {
+    fn rule(&self) -> Rule {
+        Rule::RedefFieldNotOptional
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        fn redefs(node: Node) -> Vec<Node> {
+            if node.kind() == "redef_record_decl" {
+                vec![node]
+            } else {
+                node.named_children_not("nl")
+                    .into_iter()
+                    .flat_map(redefs)
+                    .collect()
+            }
+        }
+
+        let Some(tree) = db.parse(Arc::clone(uri)) else {
+            return Vec::new();
+        };
+
+        redefs(tree.root_node())
+            .into_iter()
+            .flat_map(|node| check_redef_fields(db, uri, node))
+            .collect()
+    }
+}
+
+/// Checks that fields added with `redef record` are `&optional` or have a `&default`.
+fn check_redef_fields(db: &Database, uri: &Arc<Url>, node: Node) -> Vec<Diagnostic> {
+    let Some(source) = db.source(Arc::clone(uri)) else {
+        return Vec::new();
+    };
+
+    node.named_children("type_spec")
+        .into_iter()
+        .filter(|spec| {
+            !query::attrs(*spec, source.as_bytes())
+                .iter()
+                .any(|a| matches!(a.name.as_str(), "optional" | "default"))
+        })
+        .filter_map(|spec| {
+            let id = spec.named_child("id")?;
+            Some(Diagnostic::new_simple(
+                id.range(),
+                format!(
+                    "field '{}' added with 'redef record' must be '&optional' or have a '&default'",
+                    id.utf8_text(source.as_bytes()).ok()?
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Checks attribute values against the declaration they are attached to.
+///
+/// We check that `&default` values match the declared type (or the yield type for tables), and
+/// that `&expire_func` and `&on_change` reference functions with fitting signatures.
+fn check_attr(db: &Database, uri: &Arc<Url>, node: Node) -> Option<Diagnostic> {
+    let source = db.source(Arc::clone(uri))?;
+    let children = node.children();
+    let name = children.first()?.utf8_text(source.as_bytes()).ok()?;
+    let value = children.get(2).copied()?;
+
+    // Attributes are attached to the declaration via an `attr_list`.
+    let owner = node.parent()?;
+    let owner = if owner.kind() == "attr_list" {
+        owner.parent()?
+    } else {
+        owner
+    };
+    let typ_node = owner.named_child("type")?;
+    let typ = query::typ(typ_node, source.as_bytes())?;
+    let location = NodeLocation::from_node(Arc::clone(uri), typ_node);
+
+    match name {
+        "&default" => {
+            let expected = match typ {
+                Type::Table(_, yield_) => db.resolve_type(*yield_, Some(location))?,
+                Type::Set(_) => return None,
+                typ => db.resolve_type(typ, Some(location))?,
+            };
+            let actual = expr_type(db, uri, value, 0)?;
+            mismatch(value, &expected, &actual, "'&default'")
+        }
+        "&expire_func" | "&on_change" => {
+            let (num_index, has_yield) = match &typ {
+                Type::Table(keys, _) => (keys.len(), true),
+                Type::Set(keys) => (keys.len(), false),
+                _ => return None,
+            };
+
+            let func = db.resolve(NodeLocation::from_node(Arc::clone(uri), value))?;
+            let sig = match &func.kind {
+                DeclKind::FuncDecl(sig) | DeclKind::FuncDef(sig) => sig,
+                DeclKind::Global
+                | DeclKind::Const
+                | DeclKind::Option
+                | DeclKind::Variable
+                | DeclKind::Field
+                | DeclKind::EnumMember => {
+                    return Some(error(
+                        value,
+                        format!("value of '{name}' must be a function"),
+                    ));
+                }
+                _ => return None,
+            };
+
+            // `&expire_func` receives the table and its index, `&on_change` additionally the
+            // change type and for tables the value.
+            let (num_params, result) = if name == "&expire_func" {
+                (1 + num_index, Some(Type::Interval))
+            } else {
+                (2 + num_index + usize::from(has_yield), None)
+            };
+
+            if sig.args.len() != num_params {
+                return Some(error(
+                    value,
+                    format!(
+                        "function '{}' used with '{name}' must take {num_params} parameters, but takes {}",
+                        func.fqid,
+                        sig.args.len()
+                    ),
+                ));
+            }
+
+            if let Some(result) = result {
+                if sig.result.as_ref() != Some(&result) {
+                    return Some(error(
+                        value,
+                        format!(
+                            "function '{}' used with '{name}' must return 'interval'",
+                            func.fqid
+                        ),
+                    ));
+                }
+            }
+
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Checks that both sides of an assignment `lhs = rhs` have compatible types.
+fn check_assignment(db: &Database, uri: &Arc<Url>, node: Node) -> Option<Diagnostic> {
+    let xs = node.named_children_not("nl");
+    let lhs = xs.first().copied()?;
+    let rhs = xs.get(1).copied()?;
+
+    let expected = expr_type(db, uri, lhs, 0)?;
+    let actual = expr_type(db, uri, rhs, 0)?;
+
+    mismatch(rhs, &expected, &actual, "assignment")
+}
+
+/// Checks number and types of arguments passed to a function, event or hook.
+fn check_call(db: &Database, uri: &Arc<Url>, call: Node, callee: Node) -> Vec<Diagnostic> {
+    let Some(decl) = db.resolve(NodeLocation::from_node(Arc::clone(uri), callee)) else {
+        return Vec::new();
+    };
+
+    let Some((what, sig)) = signature(&decl) else {
+        return Vec::new();
+    };
+
+    // BiFs taking a variable number of arguments are declared with a single `va_args: any`.
+    if sig.args.iter().any(|a| a.id == "va_args") {
+        return Vec::new();
+    }
+
+    let args = call
+        .named_child("expr_list")
+        .map(|xs| xs.named_children("expr"))
+        .unwrap_or_default();
+
+    let num_required = sig.args.iter().filter(|a| !has_default(a)).count();
+    if args.len() < num_required || args.len() > sig.args.len() {
+        let expected = if num_required == sig.args.len() {
+            format!("{num_required}")
+        } else {
+            format!("{num_required} to {}", sig.args.len())
+        };
+
+        return vec![error(
+            call,
+            format!(
+                "wrong number of arguments for {what} '{}': expected {expected}, got {}",
+                decl.fqid,
+                args.len()
+            ),
+        )];
+    }
+
+    args.into_iter()
+        .zip(sig.args.iter())
+        .filter_map(|(arg, param)| {
+            let expected = db.typ(Arc::new(param.clone()))?;
+            let actual = expr_type(db, uri, arg, 0)?;
+
+            mismatch(
+                arg,
+                &expected,
+                &actual,
+                &format!("argument '{}' of {what} '{}'", param.id, decl.fqid),
+            )
+        })
+        .collect()
+}
+
+/// Checks `return` statements against the declared result of the enclosing function, event or
+/// hook.
+fn check_return(db: &Database, uri: &Arc<Url>, node: Node) -> Option<Diagnostic> {
+    // We cannot reason about e.g., `return when` which has different semantics.
+    let value = match node.named_children_not("nl").as_slice() {
+        [] => None,
+        [x] if x.kind() == "expr" => Some(*x),
+        _ => return None,
+    };
+
+    // Find the enclosing function-like. If we pass through an expression we are in a lambda
+    // which we do not handle; if we pass through a `when` the `return` has different semantics.
+    let mut parent = node.parent();
+    let f = loop {
+        let n = parent?;
+        match n.kind() {
+            "func_decl" | "hook_decl" | "event_decl" => break n,
+            "expr" => return None,
+            _ if n.children().first().is_some_and(|c| c.kind() == "when") => return None,
+            _ => parent = n.parent(),
+        }
+    };
+
+    match (f.kind(), value) {
+        ("event_decl", Some(value)) => Some(error(value, "events cannot return a value".into())),
+        ("hook_decl", Some(value)) => Some(error(value, "hooks cannot return a value".into())),
+        ("func_decl", value) => {
+            let result = f.named_child("func_params")?.named_child("type");
+
+            match (result, value) {
+                (None, Some(value)) => Some(error(
+                    value,
+                    "function without declared result type cannot return a value".into(),
+                )),
+                (Some(result), None) => {
+                    let result = type_of_type_node(db, uri, result)?;
+                    Some(error(
+                        node,
+                        format!("missing return value of type '{}'", result.fqid),
+                    ))
+                }
+                (Some(result), Some(value)) => {
+                    let expected = type_of_type_node(db, uri, result)?;
+                    let actual = expr_type(db, uri, value, 0)?;
+                    mismatch(value, &expected, &actual, "return value")
+                }
+                (None, None) => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Checks that event and hook handlers match one of their declarations.
+fn check_handlers(db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+    db.decls(Arc::clone(uri))
+        .iter()
+        .filter(|d| matches!(d.kind, DeclKind::EventDef(_) | DeclKind::HookDef(_)))
+        .filter_map(|handler| {
+            let (what, sig) = signature(handler)?;
+
+            let declarations = handler_declarations(db, uri, handler);
+            let declaration = preferred_declaration(&declarations)?;
+
+            if declarations
+                .iter()
+                .filter_map(signature)
+                .any(|(_, decl_sig)| handler_matches(db, sig, decl_sig))
+            {
+                return None;
+            }
+
+            let params = handler_params(db, handler)?;
+
+            Some(Diagnostic::new_simple(
+                params,
+                format!(
+                    "parameters of {what} '{}' do not match its declaration '{}({})'",
+                    handler.fqid,
+                    declaration.id,
+                    declared_params(db, declaration)?
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Computes a fix for a handler whose parameters at `range` do not match its declaration.
+pub(crate) fn handler_signature_fix(
+    db: &Database,
+    uri: &Arc<Url>,
+    range: Range,
+) -> Option<TextEdit> {
+    let tree = db.parse(Arc::clone(uri))?;
+    let params = tree.root_node().named_descendant_for_point_range(range)?;
+    let handler_node = params.parent()?;
+
+    let decls = db.decls(Arc::clone(uri));
+    let handler = decls.iter().find(|d| {
+        matches!(d.kind, DeclKind::EventDef(_) | DeclKind::HookDef(_))
+            && d.loc
+                .as_ref()
+                .is_some_and(|l| l.range == handler_node.range())
+    })?;
+
+    let declarations = handler_declarations(db, uri, handler);
+    let declaration = preferred_declaration(&declarations)?;
+
+    Some(TextEdit::new(
+        params.range(),
+        format!("({})", declared_params(db, declaration)?),
+    ))
+}
+
+/// Find all declarations of the given event or hook handler.
+fn handler_declarations(db: &Database, uri: &Arc<Url>, handler: &Decl) -> Vec<Decl> {
+    let explicit = db.explicit_decls_recursive(Arc::clone(uri));
+    let implicit = db.implicit_decls();
+
+    explicit
+        .iter()
+        .chain(implicit.iter())
+        .filter(|d| {
+            d.fqid == handler.fqid
+                && matches!(
+                    (&handler.kind, &d.kind),
+                    (DeclKind::EventDef(_), DeclKind::EventDecl(_))
+                        | (DeclKind::HookDef(_), DeclKind::HookDecl(_))
+                )
+        })
+        .unique()
+        .cloned()
+        .collect()
+}
+
+/// Pick the declaration to suggest for a handler; with multiple prototypes prefer the one with
+/// the most parameters.
+fn preferred_declaration(declarations: &[Decl]) -> Option<&Decl> {
+    declarations
+        .iter()
+        .max_by_key(|d| signature(d).map_or(0, |(_, s)| s.args.len()))
+}
+
+/// Checks whether a handler signature is compatible with a declared signature.
+///
+/// Handlers taking all parameters need to match the declared types positionally. Handlers can
+/// also take a subset of the declared parameters; in that case parameters are matched by name.
+fn handler_matches(db: &Database, handler: &Signature, declaration: &Signature) -> bool {
+    let same_type = |a: &Decl, b: &Decl| {
+        let (Some(a), Some(b)) = (db.typ(Arc::new(a.clone())), db.typ(Arc::new(b.clone()))) else {
+            return true;
+        };
+
+        a.fqid == b.fqid || (is_compatible(&a, &b) && is_compatible(&b, &a))
+    };
+
+    if handler.args.len() == declaration.args.len() {
+        return handler
+            .args
+            .iter()
+            .zip(declaration.args.iter())
+            .all(|(h, d)| same_type(h, d));
+    }
+
+    let mut declared = declaration.args.iter();
+    handler.args.iter().all(|h| {
+        declared
+            .by_ref()
+            .find(|d| d.id == h.id)
+            .is_some_and(|d| same_type(h, d))
+    })
+}
+
+/// Get the range of the `func_params` node of a handler.
+fn handler_params(db: &Database, handler: &Decl) -> Option<Range> {
+    let loc = handler.loc.as_ref()?;
+    let tree = db.parse(Arc::clone(&loc.uri))?;
+    tree.root_node()
+        .named_descendant_for_point_range(loc.range)?
+        .named_child("func_params")
+        .map(|n| n.range())
+}
+
+/// Get the source of the parameters of a function-like decl, e.g., `c: connection, n: count`.
+pub(crate) fn declared_params(db: &Database, decl: &Decl) -> Option<String> {
+    let (_, sig) = signature(decl)?;
+
+    sig.args
+        .iter()
+        .map(|a| {
+            let loc = a.loc.as_ref()?;
+            let tree = db.parse(Arc::clone(&loc.uri))?;
+            let source = db.source(Arc::clone(&loc.uri))?;
+            let arg = tree
+                .root_node()
+                .named_descendant_for_point_range(loc.selection_range)?;
+            Some(arg.utf8_text(source.as_bytes()).ok()?.to_string())
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|xs| xs.join(", "))
+}
+
+/// A `switch` statement.
+struct Switch<'a> {
+    /// The expression switched over.
+    expr: Node<'a>,
+
+    /// Range of `switch ( expr )`.
+    header: Range,
+
+    /// Labels of all `case`s of the switch.
+    labels: Vec<String>,
+
+    has_default: bool,
+
+    /// Indentation of the first `case` of the switch, if any.
+    case_indent: Option<String>,
+
+    /// Position of the closing `}` of the switch.
+    close: Position,
+}
+
+/// Interpret a node as a `switch` statement.
+fn switch<'a>(node: Node<'a>, source: &str) -> Option<Switch<'a>> {
+    let children = node.children();
+    let [kw, expr, .., close] = children.as_slice() else {
+        return None;
+    };
+    if kw.is_named() || kw.kind() != "switch" || expr.kind() != "expr" || close.kind() != "}" {
+        return None;
+    }
+
+    // Cases are wrapped in a `case_list`.
+    let items: Vec<_> = children
+        .iter()
+        .flat_map(|c| {
+            if c.kind() == "case_list" {
+                c.children()
+            } else {
+                vec![*c]
+            }
+        })
+        .collect();
+
+    let mut labels = Vec::new();
+    let mut has_default = false;
+    let mut case_indent = None;
+
+    for (i, item) in items.iter().enumerate() {
+        if item.is_named() {
+            continue;
+        }
+
+        match item.kind() {
+            "case" => {
+                if case_indent.is_none() {
+                    let start = item.range().start;
+                    case_indent = source
+                        .lines()
+                        .nth(usize::try_from(start.line).ok()?)
+                        .and_then(|l| l.get(..usize::try_from(start.character).ok()?))
+                        .filter(|indent| indent.trim().is_empty())
+                        .map(String::from);
+                }
+
+                let Some(label) = items.get(i + 1) else {
+                    continue;
+                };
+                let exprs = match label.kind() {
+                    "expr_list" => label.named_children("expr"),
+                    "expr" => vec![*label],
+                    _ => Vec::new(),
+                };
+                labels.extend(
+                    exprs
+                        .into_iter()
+                        .filter_map(|e| e.utf8_text(source.as_bytes()).ok())
+                        .map(String::from),
+                );
+            }
+            "default" => has_default = true,
+            _ => {}
+        }
+    }
+
+    Some(Switch {
+        expr: *expr,
+        header: Range::new(kw.range().start, expr.range().end),
+        labels,
+        has_default,
+        case_indent,
+        close: close.range().start,
+    })
+}
+
+/// Find all `switch` statements below a node.
+fn switches<'a>(node: Node<'a>, source: &str, switches_: &mut Vec<Switch<'a>>) {
+    // Do not attempt to make sense of code with syntax errors.
+    if node.kind() == "ERROR" {
+        return;
+    }
+
+    if let Some(s) = switch(node, source) {
+        switches_.push(s);
+    }
+
+    for c in node.named_children_not("nl") {
+        switches(c, source, switches_);
+    }
+}
+
+/// Find the expression switched over by the `switch` statement containing a node.
+///
+/// This also works for incomplete statements which were parsed into error nodes.
+pub(crate) fn enclosing_switch_expr(node: Node) -> Option<Node> {
+    let start = node.range().start;
+
+    let mut node = Some(node);
+    while let Some(n) = node {
+        let children = n.children();
+        let expr = children
+            .iter()
+            .tuple_windows()
+            .filter(|(kw, expr)| {
+                !kw.is_named()
+                    && kw.kind() == "switch"
+                    && expr.kind() == "expr"
+                    && expr.range().end <= start
+            })
+            .last()
+            .map(|(_, expr)| *expr);
+        if expr.is_some() {
+            return expr;
+        }
+        node = n.parent();
+    }
+    None
+}
+
+/// Get the enum type of the expression of a `switch` statement.
+pub(crate) fn switch_enum(db: &Database, uri: &Arc<Url>, expr: Node) -> Option<Arc<Decl>> {
+    expr_type(db, uri, expr, 0).filter(|typ| matches!(typ.kind, DeclKind::Enum(_)))
+}
+
+/// Get the enum members not handled by a switch, together with the enum type.
+///
+/// Returns `None` if the switch is not over an enum, or has a `default` case.
+fn missing_cases(db: &Database, uri: &Arc<Url>, switch: &Switch) -> Option<(Str, Vec<Decl>)> {
+    if switch.has_default {
+        return None;
+    }
+
+    let typ = switch_enum(db, uri, switch.expr)?;
+
+    let missing: Vec<_> = db
+        .visible_enum_members(Arc::clone(uri), typ.fqid.clone())
+        .iter()
+        .filter(|m| {
+            !switch
+                .labels
+                .iter()
+                .any(|l| l == m.fqid.as_str() || l == m.id.as_str())
+        })
+        .cloned()
+        .collect();
+
+    if missing.is_empty() {
+        return None;
+    }
+
+    Some((typ.fqid.clone(), missing))
+}
+
+/// `switch` statements over enums which do not handle all members of the enum.
+pub(crate) struct NonExhaustiveSwitch;
+
+impl Lint for NonExhaustiveSwitch {
+    fn rule(&self) -> Rule {
+        Rule::NonExhaustiveSwitch
+    }
+
+    fn check(&self, db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+        check_switches(db, uri)
+    }
+}
+
+/// Checks that `switch` statements over enums handle all members of the enum.
+fn check_switches(db: &Database, uri: &Arc<Url>) -> Vec<Diagnostic> {
+    let (Some(tree), Some(source)) = (db.parse(Arc::clone(uri)), db.source(Arc::clone(uri))) else {
+        return Vec::new();
+    };
+
+    let mut switches_ = Vec::new();
+    switches(tree.root_node(), &source, &mut switches_);
+
+    switches_
+        .iter()
+        .filter_map(|switch| {
+            let (typ, missing) = missing_cases(db, uri, switch)?;
+
+            Some(Diagnostic::new_simple(
+                switch.header,
+                format!(
+                    "switch over '{typ}' does not handle {}",
+                    missing.iter().map(|m| format!("'{}'", m.fqid)).join(", ")
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Computes a fix adding the missing cases to the switch at `range`.
+pub(crate) fn switch_cases_fix(db: &Database, uri: &Arc<Url>, range: Range) -> Option<TextEdit> {
+    let source = db.source(Arc::clone(uri))?;
+    let tree = db.parse(Arc::clone(uri))?;
+
+    let mut switches_ = Vec::new();
+    switches(tree.root_node(), &source, &mut switches_);
+    let switch = switches_.into_iter().find(|s| s.header == range)?;
+    let (_, missing) = missing_cases(db, uri, &switch)?;
+
+    let close = switch.close;
+    let before_close = source
+        .lines()
+        .nth(usize::try_from(close.line).ok()?)?
+        .get(..usize::try_from(close.character).ok()?)?;
+
+    // Indent new cases like existing ones, or one level deeper than the closing brace.
+    let indent = switch.case_indent.unwrap_or_else(|| {
+        let close_indent = &before_close[..before_close.len() - before_close.trim_start().len()];
+        format!("{close_indent}\t")
+    });
+    let cases = missing
+        .iter()
+        .map(|m| format!("{indent}case {}:\n", m.fqid))
+        .join("");
+
+    // Insert the cases on their own lines before the closing brace.
+    if before_close.trim().is_empty() {
+        let at = Position::new(close.line, 0);
+        Some(TextEdit::new(Range::new(at, at), cases))
+    } else {
+        Some(TextEdit::new(
+            Range::new(close, close),
+            format!("\n{cases}"),
+        ))
+    }
+}
+
+/// Whether the given node is an assignment expression `lhs = rhs`.
+pub(crate) fn is_assignment(node: Node) -> bool {
+    matches!(
+        node.children().as_slice(),
+        [lhs, op, rhs] if lhs.kind() == "expr" && op.kind() == "=" && rhs.kind() == "expr"
+    )
+}
+
+/// Whether the given node is a `return` statement.
+fn is_return(node: Node) -> bool {
+    node.children()
+        .first()
+        .is_some_and(|c| !c.is_named() && c.kind() == "return")
+}
+
+/// If the node is a call `f(...)`, `event f(...)` or `hook f(...)` return the node holding `f`.
+pub(crate) fn call_target(node: Node) -> Option<Node> {
+    let children = node.children();
+    let children = match children.as_slice() {
+        [kw, rest @ ..] if !kw.is_named() && matches!(kw.kind(), "event" | "hook") => rest,
+        xs => xs,
+    };
+
+    match children {
+        [id, open, ..] if id.kind() == "id" && open.kind() == "(" => Some(*id),
+        _ => None,
+    }
+}
+
+/// Extract the signature of a function-like decl together with a human-readable kind.
+pub(crate) fn signature(decl: &Decl) -> Option<(&'static str, &Signature)> {
+    match &decl.kind {
+        DeclKind::FuncDecl(s) | DeclKind::FuncDef(s) => Some(("function", s)),
+        DeclKind::HookDecl(s) | DeclKind::HookDef(s) => Some(("hook", s)),
+        DeclKind::EventDecl(s) | DeclKind::EventDef(s) => Some(("event", s)),
+        _ => None,
+    }
+}
+
+/// Whether a function parameter has a `&default` and can be omitted.
+fn has_default(param: &Decl) -> bool {
+    param.attrs.iter().any(|a| a.name == "default")
+}
+
+fn type_of_type_node(db: &Database, uri: &Arc<Url>, typ: Node) -> Option<Arc<Decl>> {
+    let source = db.source(Arc::clone(uri))?;
+    query::typ(typ, source.as_bytes())
+        .and_then(|t| db.resolve_type(t, Some(NodeLocation::from_node(Arc::clone(uri), typ))))
+}
+
+/// Compute the type of an expression.
+///
+/// This only handles expressions whose type we can determine with confidence, e.g., literals,
+/// identifiers, field accesses, casts and calls; for anything else `None` is returned.
+pub(crate) fn expr_type(
+    db: &Database,
+    uri: &Arc<Url>,
+    node: Node,
+    depth: usize,
+) -> Option<Arc<Decl>> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+
+    let location = NodeLocation::from_node(Arc::clone(uri), node);
+
+    match node.kind() {
+        "integer" | "hostname" | "floatp" | "ipv4" | "ipv6" | "interval" | "port" | "string"
+        | "hex" | "constant" => {
+            return db
+                .resolve(location)
+                .filter(|d| matches!(d.kind, DeclKind::Builtin(_)));
+        }
+        "id" | "field_access" => {
+            let decl = db.resolve(location)?;
+            return decl_type(db, decl, depth + 1);
+        }
+        "field_check" => return db.resolve_type(Type::Bool, Some(location)),
+        "expr" => {}
+        _ => return None,
+    }
+
+    match node.children().as_slice() {
+        [x] if x.is_named() => expr_type(db, uri, *x, depth),
+        [open, x, close] if open.kind() == "(" && close.kind() == ")" && x.kind() == "expr" => {
+            expr_type(db, uri, *x, depth)
+        }
+        [x, op, typ] if x.kind() == "expr" && op.kind() == "as" && typ.kind() == "type" => {
+            type_of_type_node(db, uri, *typ)
+        }
+        _ => {
+            let callee = call_target(node)?;
+            let decl = db.resolve(NodeLocation::from_node(Arc::clone(uri), callee))?;
+            match &decl.kind {
+                DeclKind::FuncDecl(sig) | DeclKind::FuncDef(sig) => {
+                    db.resolve_type(sig.result.clone()?, Some(location))
+                }
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Compute the type of a value referenced by a decl.
+fn decl_type(db: &Database, decl: Arc<Decl>, depth: usize) -> Option<Arc<Decl>> {
+    match &decl.kind {
+        DeclKind::Global | DeclKind::Const | DeclKind::Option | DeclKind::Variable => {}
+        DeclKind::Field | DeclKind::Index(..) | DeclKind::EnumMember => return db.typ(decl),
+        _ => return None,
+    }
+
+    // For variables with explicit type use the declared type. For untyped variables only trust
+    // the initializer if we can type it ourselves.
+    let loc = decl.loc.as_ref()?;
+    let tree = db.parse(Arc::clone(&loc.uri))?;
+    let node = tree
+        .root_node()
+        .named_descendant_for_point_range(loc.range)?;
+
+    match node.kind() {
+        "var_decl" | "const_decl" | "option_decl" if node.named_child("type").is_none() => {
+            let init = node.named_child("initializer")?.named_child("expr")?;
+            expr_type(db, &loc.uri, init, depth + 1)
+        }
+        _ => db.typ(decl),
+    }
+}
+
+/// Coarse classification of types we can compare reliably.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Class<'a> {
+    Builtin(&'a str),
+    Enum(&'a str),
+    Record(&'a str),
+}
+
+fn classify(decl: &Decl) -> Option<Class<'_>> {
+    match &decl.kind {
+        DeclKind::Builtin(typ) => match typ {
+            Type::Addr
+            | Type::Bool
+            | Type::Count
+            | Type::Double
+            | Type::Int
+            | Type::Interval
+            | Type::String
+            | Type::Subnet
+            | Type::Pattern
+            | Type::Port
+            | Type::Time => Some(Class::Builtin(&decl.fqid)),
+            // Integer literals are typed with a placeholder, see `resolve`.
+            Type::Id(id) if id == "<integer>" => Some(Class::Builtin(&decl.fqid)),
+            _ => None,
+        },
+        DeclKind::Enum(_) => Some(Class::Enum(&decl.fqid)),
+        // Type aliases like `type X: count;` are exposed as types without fields, so only
+        // consider types with fields as records.
+        DeclKind::Type(fields) if !fields.is_empty() => Some(Class::Record(&decl.fqid)),
+        _ => None,
+    }
+}
+
+fn is_compatible(expected: &Decl, actual: &Decl) -> bool {
+    let (Some(expected), Some(actual)) = (classify(expected), classify(actual)) else {
+        return true;
+    };
+
+    if expected == actual {
+        return true;
+    }
+
+    // Zeek promotes arithmetic types.
+    matches!(
+        (expected, actual),
+        (
+            Class::Builtin("count" | "int" | "double"),
+            Class::Builtin("<integer>")
+        ) | (Class::Builtin("int" | "double"), Class::Builtin("count"))
+            | (Class::Builtin("double"), Class::Builtin("int"))
+    )
+}
+
+fn mismatch(node: Node, expected: &Decl, actual: &Decl, what: &str) -> Option<Diagnostic> {
+    if is_compatible(expected, actual) {
+        return None;
+    }
+
+    Some(error(
+        node,
+        format!(
+            "type mismatch in {what}: expected '{}', got '{}'",
+            expected.fqid, actual.fqid
+        ),
+    ))
+}
+
+fn error(node: Node, message: String) -> Diagnostic {
+    Diagnostic::new_simple(node.range(), message)
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::sync::Arc;
+
+    use tower_lsp::lsp_types::Url;
+
+    use crate::{lint::Lint, lsp::TestDatabase, parse::Parse};
+
+    fn diagnostics(source: &str) -> Vec<(u32, String)> {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file((*uri).clone(), source);
+
+        [&super::TypeMismatch as &dyn Lint, &super::HandlerSignature]
+            .iter()
+            .flat_map(|lint| lint.check(&db.0, &uri))
+            .map(|d| (d.range.start.line, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn assignment() {
+        assert_eq!(
+            diagnostics(
+                r#"
+global a: count = "abc";
+global b: count = 42;
+global c: double = b;
+global d: string = b;
+event zeek_init() { b = "abc"; b = 1; }
+"#
+            ),
+            vec![
+                (
+                    1,
+                    "type mismatch in initialization: expected 'count', got 'string'".into()
+                ),
+                (
+                    4,
+                    "type mismatch in initialization: expected 'string', got 'count'".into()
+                ),
+                (
+                    5,
+                    "type mismatch in assignment: expected 'count', got 'string'".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn call() {
+        assert_eq!(
+            diagnostics(
+                r#"
+function f(x: count, y: string &default="") {}
+global e: event(x: count);
+f(1);
+f(1, "a");
+f();
+f(1, "a", 3);
+f("a");
+event zeek_init() { event e(1, 2); }
+"#
+            ),
+            vec![
+                (
+                    5,
+                    "wrong number of arguments for function 'f': expected 1 to 2, got 0".into()
+                ),
+                (
+                    6,
+                    "wrong number of arguments for function 'f': expected 1 to 2, got 3".into()
+                ),
+                (
+                    7,
+                    "type mismatch in argument 'x' of function 'f': expected 'count', got 'string'"
+                        .into()
+                ),
+                (
+                    8,
+                    "wrong number of arguments for event 'e': expected 1, got 2".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn attrs() {
+        assert_eq!(
+            diagnostics(
+                r#"
+function expire(t: table[count] of string, k: count): interval { return 0secs; }
+function bad_expire(t: table[count] of string): count { return 0; }
+function changed(t: set[count], tpe: TableChange, k: count) {}
+global x: count = 1;
+global a: table[count] of string &default="" &expire_func=expire;
+global b: table[count] of string &default=1;
+global c: table[count] of string &expire_func=bad_expire;
+global d: set[count] &on_change=changed;
+global e: set[count] &on_change=x;
+type R: record { r: count &default="a"; };
+"#
+            ),
+            vec![
+                (
+                    6,
+                    "type mismatch in '&default': expected 'string', got '<integer>'".into()
+                ),
+                (
+                    7,
+                    "function 'bad_expire' used with '&expire_func' must take 2 parameters, but takes 1"
+                        .into()
+                ),
+                (9, "value of '&on_change' must be a function".into()),
+                (
+                    10,
+                    "type mismatch in '&default': expected 'count', got 'string'".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn returns() {
+        assert_eq!(
+            diagnostics(
+                r#"
+function f1(): count { return 1; }
+function f2(): count { return "a"; }
+function f3(): count { return; }
+function f4() { return 1; }
+function f5() { return; }
+event e1() { return; }
+event e2() { return 1; }
+hook h() { return 1; }
+"#
+            ),
+            vec![
+                (
+                    2,
+                    "type mismatch in return value: expected 'count', got 'string'".into()
+                ),
+                (3, "missing return value of type 'count'".into()),
+                (
+                    4,
+                    "function without declared result type cannot return a value".into()
+                ),
+                (7, "events cannot return a value".into()),
+                (8, "hooks cannot return a value".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn handlers() {
+        assert_eq!(
+            diagnostics(
+                r"
+global e: event(c: count, s: string);
+global h: hook(c: count);
+event e(c: count, s: string) {}
+event e(x: count, y: string) {}
+event e(s: string) {}
+event e(c: count) {}
+event e(s: string, c: count) {}
+event e(c: string, s: string) {}
+event e(c: count, s: string, x: count) {}
+hook h(c: count) {}
+hook h(c: string) {}
+"
+            ),
+            vec![
+                (
+                    7,
+                    "parameters of event 'e' do not match its declaration 'e(c: count, s: string)'"
+                        .into()
+                ),
+                (
+                    8,
+                    "parameters of event 'e' do not match its declaration 'e(c: count, s: string)'"
+                        .into()
+                ),
+                (
+                    9,
+                    "parameters of event 'e' do not match its declaration 'e(c: count, s: string)'"
+                        .into()
+                ),
+                (
+                    11,
+                    "parameters of hook 'h' do not match its declaration 'h(c: count)'".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn handler_signature_fix() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            "global e: event(c: count, s: string);\nevent e(s: count) {}",
+        );
+
+        let diag = super::HandlerSignature
+            .check(&db.0, &uri)
+            .into_iter()
+            .next()
+            .unwrap();
+
+        let fix = super::handler_signature_fix(&db.0, &uri, diag.range).unwrap();
+        assert_eq!(fix.range, diag.range);
+        assert_eq!(fix.new_text, "(c: count, s: string)");
+    }
+
+    #[test]
+    fn switches() {
+        let mut db = TestDatabase::default();
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        let source = r##"event e() {
+    print "#"; switch ( f(x) ) {
+    # case C:
+    case A, Foo::B:
+        print "case D:";
+        switch ( y ) { default: break; }
+        break;
+    case E: { local z = T(1); }
+    }
+    switch(y) { default: break; }
+}"##;
+        db.add_file((*uri).clone(), source);
+
+        let tree = db.0.parse(uri).unwrap();
+        let mut switches = Vec::new();
+        super::switches(tree.root_node(), source, &mut switches);
+
+        assert_eq!(switches.len(), 3);
+        assert_eq!(switches[0].labels, ["A", "Foo::B", "E"]);
+        assert!(!switches[0].has_default);
+        assert_eq!(switches[0].case_indent.as_deref(), Some("    "));
+        assert_eq!(switches[0].header.start.character, 15);
+        assert_eq!(switches[0].header.end.character, 30);
+        assert!(switches[1].has_default);
+        assert!(switches[2].has_default);
+        assert_eq!(switches[2].header.start.line, 9);
+    }
+
+    #[test]
+    fn switch_exhaustiveness() {
+        let mut db = TestDatabase::default();
+
+        let uri = Arc::new(Url::from_file_path("/x.zeek").unwrap());
+        db.add_file(
+            (*uri).clone(),
+            "@load ./y
+type E: enum { A, B, C };
+event zeek_init() {
+    local e: E = A;
+    switch ( e ) {
+    case A:
+        break;
+    }
+    switch ( e ) {
+    case A, B, C, D:
+        break;
+    }
+    switch ( e ) {
+    default:
+        break;
+    }
+}",
+        );
+        db.add_file(
+            Url::from_file_path("/y.zeek").unwrap(),
+            "redef enum E += { D };",
+        );
+        db.add_file(
+            Url::from_file_path("/z.zeek").unwrap(),
+            "redef enum E += { F };",
+        );
+
+        let diags = super::check_switches(&db.0, &uri);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].range.start.line, 4);
+        assert_eq!(
+            diags[0].message,
+            "switch over 'E' does not handle 'B', 'C', 'D'"
+        );
+
+        let fix = super::switch_cases_fix(&db.0, &uri, diags[0].range).unwrap();
+        assert_eq!(fix.range.start.line, 7);
+        assert_eq!(fix.new_text, "    case B:\n    case C:\n    case D:\n");
+    }
+}
@@ -9,9 +9,12 @@ use std::{
 
 use eyre::{eyre, Result};
 use path_clean::PathClean;
+use semver::Version;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use walkdir::WalkDir;
 
+use crate::preproc;
+
 async fn zeek_config<I, S>(args: I) -> Result<std::process::Output>
 where
     I: IntoIterator<Item = S>,
@@ -76,6 +79,10 @@ pub async fn prefixes(zeekpath: Option<String>) -> Result<Vec<PathBuf>> {
         .await
         .into_iter()
         .flatten()
+        .chain(
+            // Packages installed with zkg can be loaded by name.
+            crate::zkg::script_dir().await.ok().map(Ok),
+        )
         .collect::<Result<_>>()?
     };
 
@@ -94,6 +101,33 @@ pub async fn prefixes(zeekpath: Option<String>) -> Result<Vec<PathBuf>> {
     Ok(ys)
 }
 
+/// Get the version of the installed Zeek.
+///
+/// # Errors
+///
+/// Will return `Err` if neither `zeek-config` nor `zeek` can be queried, or if the version cannot
+/// be parsed.
+pub async fn version() -> Result<Version> {
+    let output = match zeek_config(["--version"]).await {
+        Ok(output) if output.status.success() => output,
+        _ => tokio::process::Command::new("zeek")
+            .arg("--version")
+            .output()
+            .await
+            .map_err(|_| eyre!("zeek not found in PATH"))?,
+    };
+
+    let output = str::from_utf8(&output.stdout)?;
+    parse_version(output).ok_or_else(|| eyre!("could not parse Zeek version from '{output}'"))
+}
+
+/// Parse the output of `zeek-config --version`, e.g., `6.2.1`, or `zeek --version`, e.g.,
+/// `zeek version 6.2.1`.
+fn parse_version(output: &str) -> Option<Version> {
+    let version = output.lines().next()?.split_whitespace().last()?;
+    preproc::parse_version(version)
+}
+
 #[derive(Debug)]
 pub struct CheckResult {
     pub file: String,
@@ -180,7 +214,7 @@ pub async fn system_files() -> Result<Vec<SystemFile>> {
                 .filter_map(std::result::Result::ok)
                 .filter(|e| !e.file_type().is_dir())
                 .filter_map(|f| {
-                    if f.path().extension()? != "zeek" {
+                    if !matches!(f.path().extension()?.to_str()?, "zeek" | "sig") {
                         return None;
                     }
 
@@ -191,15 +225,28 @@ pub async fn system_files() -> Result<Vec<SystemFile>> {
         .collect())
 }
 
-pub(crate) fn essential_input_files() -> Vec<&'static str> {
-    // TODO(bbannier): does this function need a flag for bare mode?
-    vec![
-        "base/init-bare.zeek",
-        "base/init-frameworks-and-bifs.zeek",
-        "base/init-default.zeek",
-        "builtin-plugins/__preload__.zeek",
-        "builtin-plugins/__load__.zeek",
-    ]
+/// Get the files Zeek loads implicitly.
+///
+/// If the Zeek version is unknown we assume a recent version. In bare mode (`zeek -b`) only the
+/// scripts needed to initialize Zeek are loaded.
+pub(crate) fn essential_input_files(version: Option<&Version>, bare: bool) -> Vec<&'static str> {
+    let mut files = vec!["base/init-bare.zeek", "base/init-frameworks-and-bifs.zeek"];
+
+    if bare {
+        return files;
+    }
+
+    files.push("base/init-default.zeek");
+
+    // Scripts of builtin plugins are only loaded since Zeek 4.0.
+    if version.is_none_or(|v| v.major >= 4) {
+        files.extend([
+            "builtin-plugins/__preload__.zeek",
+            "builtin-plugins/__load__.zeek",
+        ]);
+    }
+
+    files
 }
 
 pub(crate) async fn has_format() -> bool {
@@ -253,6 +300,38 @@ mod test {
             .exists());
     }
 
+    #[test]
+    fn parse_version() {
+        use semver::Version;
+
+        assert_eq!(zeek::parse_version("6.2.1\n"), Some(Version::new(6, 2, 1)));
+        assert_eq!(
+            zeek::parse_version("zeek version 7.0.0-dev.42\n"),
+            Some(Version::new(7, 0, 0))
+        );
+        assert_eq!(zeek::parse_version(""), None);
+    }
+
+    #[test]
+    fn essential_input_files() {
+        use semver::Version;
+
+        assert_eq!(
+            zeek::essential_input_files(None, false),
+            zeek::essential_input_files(Some(&Version::new(6, 0, 0)), false)
+        );
+        assert!(
+            !zeek::essential_input_files(Some(&Version::new(3, 2, 0)), false)
+                .iter()
+                .any(|f| f.starts_with("builtin-plugins"))
+        );
+
+        assert_eq!(
+            zeek::essential_input_files(None, true),
+            vec!["base/init-bare.zeek", "base/init-frameworks-and-bifs.zeek"]
+        );
+    }
+
     #[tokio::test]
     async fn system_files() {
         let files = zeek::system_files().await.expect("can read system files");
@@ -0,0 +1,311 @@
+use std::{
+    path::{Path, PathBuf},
+    str,
+};
+
+use eyre::{eyre, Result};
+use walkdir::WalkDir;
+
+use crate::Str;
+
+/// Name of the metadata file of a zkg package.
+pub const META_FILE: &str = "zkg.meta";
+
+/// A zkg package, see <https://docs.zeek.org/projects/package-manager/en/stable/package.html>.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Package {
+    /// Name of the package, i.e., the name of the directory containing `zkg.meta`.
+    pub name: Str,
+
+    /// Directory containing `zkg.meta`.
+    pub dir: PathBuf,
+
+    pub description: Option<Str>,
+    pub version: Option<Str>,
+
+    /// Directory containing the package's scripts, relative to `dir`.
+    pub script_dir: Option<PathBuf>,
+
+    /// Directory containing the package's plugin, relative to `dir`.
+    pub plugin_dir: Option<PathBuf>,
+
+    /// Dependencies of the package as `<name> <version constraint>`.
+    pub depends: Vec<Str>,
+}
+
+impl Package {
+    /// Parse a package from the contents of its `zkg.meta` file in `dir`.
+    ///
+    /// Returns `None` if the metadata contains no `[package]` section.
+    #[must_use]
+    pub fn parse(dir: &Path, meta: &str) -> Option<Self> {
+        let name = dir.file_name()?.to_string_lossy();
+
+        let entries = ini(meta);
+        let get = |key: &str| {
+            entries
+                .iter()
+                .find(|(section, k, _)| section == "package" && k == key)
+                .map(|(_, _, v)| v.as_str())
+                .filter(|v| !v.is_empty())
+        };
+
+        if !meta.lines().any(|l| l.trim() == "[package]") {
+            return None;
+        }
+
+        Some(Self {
+            name: Str::from(name.as_ref()),
+            dir: dir.to_path_buf(),
+            description: get("description").map(Str::from),
+            version: get("version").map(Str::from),
+            script_dir: get("script_dir").map(PathBuf::from),
+            plugin_dir: get("plugin_dir").map(PathBuf::from),
+            depends: get("depends")
+                .map(|d| {
+                    d.lines()
+                        .map(str::trim)
+                        .filter(|l| !l.is_empty())
+                        .map(Str::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Get the `__load__.zeek` script loaded when loading the package by name.
+    #[must_use]
+    pub fn load_file(&self) -> PathBuf {
+        self.dir
+            .join(self.script_dir.as_deref().unwrap_or_else(|| Path::new("")))
+            .join("__load__.zeek")
+    }
+
+    /// Render the package metadata as markdown.
+    #[must_use]
+    pub fn documentation(&self) -> String {
+        let mut docs = vec![format!("### package `{}`", self.name)];
+
+        if let Some(description) = &self.description {
+            docs.push(description.to_string());
+        }
+
+        if let Some(version) = &self.version {
+            docs.push(format!("- version: `{version}`"));
+        }
+
+        if let Some(script_dir) = &self.script_dir {
+            docs.push(format!("- script_dir: `{}`", script_dir.display()));
+        }
+
+        if let Some(plugin_dir) = &self.plugin_dir {
+            docs.push(format!("- plugin_dir: `{}`", plugin_dir.display()));
+        }
+
+        if !self.depends.is_empty() {
+            let depends: Vec<_> = self.depends.iter().map(|d| format!("`{d}`")).collect();
+            docs.push(format!("- depends: {}", depends.join(", ")));
+        }
+
+        docs.join("\n")
+    }
+}
+
+/// Parse an INI file into `(section, key, value)` entries.
+///
+/// Values can span multiple lines if continuation lines are indented.
+pub(crate) fn ini(source: &str) -> Vec<(String, String, String)> {
+    let mut entries: Vec<(String, String, String)> = Vec::new();
+    let mut section = String::new();
+
+    for line in source.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with(char::is_whitespace) {
+            if let Some((_, _, value)) = entries.last_mut() {
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(trimmed);
+            }
+            continue;
+        }
+
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+
+        if let Some((key, value)) = trimmed.split_once('=').or_else(|| trimmed.split_once(':')) {
+            entries.push((
+                section.clone(),
+                key.trim().to_string(),
+                value.trim().to_string(),
+            ));
+        }
+    }
+
+    entries
+}
+
+/// Find all packages below the given directory.
+#[must_use]
+pub fn packages_in(dir: &Path) -> Vec<Package> {
+    WalkDir::new(dir)
+        .into_iter()
+        // Do not descend into hidden directories like `.git`, or build directories.
+        .filter_entry(|e| e.depth() == 0 || !is_ignored_dir(e))
+        .filter_map(std::result::Result::ok)
+        .filter(|e| e.file_type().is_file() && e.file_name() == META_FILE)
+        .filter_map(|e| {
+            let dir = e.path().parent()?;
+            let meta = std::fs::read_to_string(e.path()).ok()?;
+            Package::parse(dir, &meta)
+        })
+        .collect()
+}
+
+/// Whether a directory cannot contain packages of interest.
+fn is_ignored_dir(entry: &walkdir::DirEntry) -> bool {
+    entry.file_type().is_dir()
+        && entry
+            .file_name()
+            .to_str()
+            .is_some_and(|n| n.starts_with('.') || matches!(n, "build" | "target" | "node_modules"))
+}
+
+/// Paths configured for zkg.
+struct Config {
+    state_dir: Option<PathBuf>,
+    script_dir: Option<PathBuf>,
+}
+
+async fn config() -> Result<Config> {
+    let output = tokio::process::Command::new("zkg")
+        .arg("config")
+        .output()
+        .await
+        .map_err(|_| eyre!("zkg not found in PATH"))?;
+
+    if !output.status.success() {
+        return Err(eyre!("'zkg config' failed"));
+    }
+
+    let entries = ini(str::from_utf8(&output.stdout)?);
+    let get = |key: &str| {
+        entries
+            .iter()
+            .find(|(section, k, v)| section == "paths" && k == key && !v.is_empty())
+            .map(|(_, _, v)| PathBuf::from(v))
+    };
+
+    Ok(Config {
+        state_dir: get("state_dir"),
+        script_dir: get("script_dir"),
+    })
+}
+
+/// Get the directory zkg installs package scripts into.
+///
+/// Packages installed there can be loaded by name.
+///
+/// # Errors
+///
+/// Will return `Err` if zkg cannot be queried.
+pub async fn script_dir() -> Result<PathBuf> {
+    config()
+        .await?
+        .script_dir
+        .map(|d| d.join("packages"))
+        .ok_or_else(|| eyre!("zkg has no configured script_dir"))
+}
+
+/// Get the packages installed with zkg.
+///
+/// # Errors
+///
+/// Will return `Err` if zkg cannot be queried.
+pub async fn installed_packages() -> Result<Vec<Package>> {
+    let state_dir = config()
+        .await?
+        .state_dir
+        .ok_or_else(|| eyre!("zkg has no configured state_dir"))?;
+
+    let clones = state_dir.join("clones").join("package");
+
+    Ok(tokio::task::spawn_blocking(move || packages_in(&clones)).await?)
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(clippy::unwrap_used)]
+
+    use std::path::{Path, PathBuf};
+
+    use crate::{
+        zkg::{packages_in, Package},
+        Str,
+    };
+
+    const META: &str = "[package]
+description = A package
+  spanning lines.
+script_dir = scripts
+plugin_dir = build/plugin
+depends =
+  zeek >=6.0.0
+  other-package *
+";
+
+    #[test]
+    fn parse() {
+        let package = Package::parse(Path::new("/x/my-package"), META).unwrap();
+
+        assert_eq!(package.name.as_str(), "my-package");
+        assert_eq!(
+            package.description.as_deref(),
+            Some("A package\nspanning lines.")
+        );
+        assert_eq!(package.script_dir, Some(PathBuf::from("scripts")));
+        assert_eq!(package.plugin_dir, Some(PathBuf::from("build/plugin")));
+        assert_eq!(
+            package.depends.iter().map(Str::as_str).collect::<Vec<_>>(),
+            ["zeek >=6.0.0", "other-package *"]
+        );
+        assert_eq!(
+            package.load_file(),
+            PathBuf::from("/x/my-package/scripts/__load__.zeek")
+        );
+
+        let package = Package::parse(Path::new("/x/y"), "[package]\n").unwrap();
+        assert_eq!(package.script_dir, None);
+        assert_eq!(package.load_file(), PathBuf::from("/x/y/__load__.zeek"));
+
+        assert_eq!(Package::parse(Path::new("/x/y"), "[other]\na = b\n"), None);
+    }
+
+    #[test]
+    fn find() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("my-package");
+        std::fs::create_dir(&package).unwrap();
+        std::fs::write(package.join("zkg.meta"), META).unwrap();
+
+        let packages = packages_in(dir.path());
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name.as_str(), "my-package");
+        assert_eq!(packages[0].dir, package);
+
+        // Hidden and build directories are not searched.
+        for ignored in [".git", "build"] {
+            let package = dir.path().join(ignored).join("other-package");
+            std::fs::create_dir_all(&package).unwrap();
+            std::fs::write(package.join("zkg.meta"), META).unwrap();
+        }
+        assert_eq!(packages_in(dir.path()).len(), 1);
+    }
+}
@@ -18,6 +18,8 @@ import {
   Executable,
   LanguageClient,
   LanguageClientOptions,
+  Location,
+  Position,
   ServerOptions,
 } from "vscode-languageclient/node";
 import { XzReadableStream } from "xz-decompress";
@@ -370,6 +372,10 @@ export async function activate(context: ExtensionContext): Promise<void> {
   const semantic_highlighting = configuration.get<boolean>(
     "semantic_highlighting.enabled",
   );
+  const unused_handler_parameters = configuration.get<boolean>(
+    "unusedHandlerParameters.enabled",
+  );
+  const lints = configuration.get<Record<string, string>>("lints");
   const debug_ast_nodes = configuration.get<boolean>("debug.AST_nodes");
 
   const clientOptions: LanguageClientOptions = {
@@ -381,6 +387,8 @@ export async function activate(context: ExtensionContext): Promise<void> {
       references,
       rename,
       semantic_highlighting,
+      unused_handler_parameters,
+      lints,
       debug_ast_nodes,
     },
   };
@@ -397,6 +405,20 @@ export async function activate(context: ExtensionContext): Promise<void> {
     clientOptions,
   );
 
+  // Show lists of locations the server sends with code lenses, e.g., event handlers.
+  context.subscriptions.push(
+    commands.registerCommand(
+      "zeek.showLocations",
+      (uri: string, position: Position, locations: Location[]) =>
+        commands.executeCommand(
+          "editor.action.showReferences",
+          CLIENT.protocol2CodeConverter.asUri(uri),
+          CLIENT.protocol2CodeConverter.asPosition(position),
+          locations.map((l) => CLIENT.protocol2CodeConverter.asLocation(l)),
+        ),
+    ),
+  );
+
   log.info("Starting Zeek Language Server...");
   CLIENT.start();
 }